@@ -5,6 +5,13 @@
 layout(std140) uniform;
 
 uniform sampler2D tex;
+uniform sampler2D ambientMap;
+uniform sampler2D specularMap;
+uniform sampler2D emissiveMap;
+uniform sampler2D shininessMap;
+uniform sampler2D dissolveMap;
+uniform sampler2D normalMap;
+uniform sampler2D metallicRoughnessMap;
 uniform sampler2D shadowMap;
 uniform sampler1D distribution;
 
@@ -18,14 +25,49 @@ uniform float frustumSize;
 uniform vec3 ambientColor;
 uniform vec3 diffuseColor;
 uniform vec3 specularColor;
+uniform vec3 emissiveColor;
+uniform vec3 transmissionFilter;
+uniform vec3 viewPosition;
+uniform float shininess;
+uniform float opticalDensity;
+uniform float dissolve;
+// 0 ignores opacity, 1 cuts out texels below alphaCutoff and draws the rest
+// opaque, 2 blends.
+uniform int alphaMode;
+uniform float alphaCutoff;
+uniform int illuminationModel;
+uniform bool metallicRoughness;
+uniform float metallic;
+uniform float roughness;
+uniform bool hasDiffuseMap;
+uniform bool hasAmbientMap;
+uniform bool hasSpecularMap;
+uniform bool hasEmissiveMap;
+uniform bool hasShininessMap;
+uniform bool hasDissolveMap;
+uniform bool hasNormalMap;
+uniform bool hasMetallicRoughnessMap;
 
 in vec2 fragTexCoord;
 in vec3 surfaceNormal;
+in vec3 surfaceTangent;
+in float tangentSign;
 in vec4 worldPos;
 in vec4 fragPosLightSpace;
 
 out vec4 finalColor;
 
+// MikkTSpace reconstruction: the bitangent is rebuilt per pixel from the
+// interpolated, unnormalized normal and tangent.
+vec3 perturb_normal(vec3 normal) {
+    if(!hasNormalMap)
+        return normalize(normal);
+
+    vec3 tangentNormal = texture(normalMap, fragTexCoord).xyz * 2.0 - 1.0;
+    vec3 bitangent = tangentSign * cross(normal, surfaceTangent);
+    return normalize(tangentNormal.x * surfaceTangent + tangentNormal.y * bitangent + tangentNormal.z * normal);
+}
+
 vec2 RandomDirection(float u) {
     return texture(distribution, u).xy * 2 - vec2(1);
 }
@@ -105,15 +147,27 @@ float compute_shadow(vec4 fragPosLightSpace, float uvLightSize, float bias) {
 void main() {
 
     vec4 textureSample = texture(tex, fragTexCoord);
-    vec2 samplerSize = textureSize(tex, 0);
 
-    if(samplerSize.x > 1 && samplerSize.y > 1 && textureSample.a < 0.1)
-        discard;
+    float opacity = dissolve;
+    if(hasDiffuseMap)
+        opacity *= textureSample.a;
+    if(hasDissolveMap)
+        opacity *= texture(dissolveMap, fragTexCoord).r;
+
+    if(alphaMode == 0)
+        opacity = 1.0;
+    else if(alphaMode == 1) {
+        if(opacity < alphaCutoff)
+            discard;
+        opacity = 1.0;
+    }
 
     vec4 AmbientColor = vec4(ambientColor, 1.0) * ambientIntensity;
+    if(hasAmbientMap)
+        AmbientColor *= texture(ambientMap, fragTexCoord);
 
     vec3 toLightVector = lightPosition - worldPos.xyz;
-    vec3 unitNormal = normalize(surfaceNormal);
+    vec3 unitNormal = perturb_normal(surfaceNormal);
     vec3 unitLightPosition = normalize(toLightVector);
 
     float nDotL = dot(unitNormal, unitLightPosition);
@@ -121,16 +175,83 @@ void main() {
 
     vec4 DiffuseColor = vec4(lightColor, 1.0) * vec4(diffuseColor, 1.0) * brightness;
 
-    if(samplerSize.x > 1 && samplerSize.y > 1) {
+    if(hasDiffuseMap) {
         AmbientColor += textureSample * ambientIntensity;
         DiffuseColor *= textureSample;
     }
 
+    // glTF materials: metals have no diffuse term and tint their highlight
+    // with the base color, while dielectrics reflect about 4%.
+    float metallicValue = metallic;
+    float roughnessValue = roughness;
+    if(hasMetallicRoughnessMap) {
+        vec4 metallicRoughnessSample = texture(metallicRoughnessMap, fragTexCoord);
+        roughnessValue *= metallicRoughnessSample.g;
+        metallicValue *= metallicRoughnessSample.b;
+    }
+    roughnessValue = max(roughnessValue, 0.05);
+
+    vec3 baseColor = hasDiffuseMap ? diffuseColor * textureSample.rgb : diffuseColor;
+    if(metallicRoughness)
+        DiffuseColor.rgb *= 1.0 - metallicValue;
+
     float bias = max(0.05 * (1.0 - dot(unitNormal, unitLightPosition)), 0.001);
 
     float shadow = compute_shadow(fragPosLightSpace, uvLightSize / frustumSize, bias);
 
+    // illum 2 and up add a Blinn-Phong highlight; 5 and 7 weight it by
+    // Schlick's Fresnel term derived from Ni.
+    vec3 SpecularColor = vec3(0.0);
+    if(illuminationModel >= 2 && nDotL > 0.0) {
+        vec3 unitToCamera = normalize(viewPosition - worldPos.xyz);
+        vec3 halfway = normalize(unitLightPosition + unitToCamera);
+
+        float exponent = shininess;
+        if(hasShininessMap)
+            exponent *= texture(shininessMap, fragTexCoord).r;
+        exponent = max(exponent, 1.0);
+
+        vec3 specularSample = hasSpecularMap ? texture(specularMap, fragTexCoord).rgb : vec3(1.0);
+        vec3 specularTint = specularColor * specularSample;
+
+        // Roughness maps onto the Blinn-Phong exponent matching a GGX lobe
+        // of the same width.
+        if(metallicRoughness) {
+            float alpha = roughnessValue * roughnessValue;
+            exponent = max(2.0 / (alpha * alpha) - 2.0, 1.0);
+            specularTint = mix(vec3(0.04), baseColor, metallicValue);
+        }
+
+        SpecularColor = lightColor * specularTint * pow(max(dot(unitNormal, halfway), 0.0), exponent);
+
+        if(illuminationModel == 5 || illuminationModel == 7) {
+            float f0 = pow((opticalDensity - 1.0) / (opticalDensity + 1.0), 2.0);
+            float fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(unitToCamera, halfway), 0.0), 5.0);
+            SpecularColor *= fresnel;
+        }
+
+        SpecularColor *= shadow;
+    }
+
+    vec3 EmissiveColor = emissiveColor;
+    if(hasEmissiveMap)
+        EmissiveColor *= texture(emissiveMap, fragTexCoord).rgb;
+
+    // illum 0 is a constant color with no lighting at all.
+    if(illuminationModel == 0) {
+        vec3 flatColor = diffuseColor;
+        if(hasDiffuseMap)
+            flatColor *= textureSample.rgb;
+        finalColor = vec4(flatColor + EmissiveColor, opacity);
+        return;
+    }
+
     AmbientColor += shadow;
 
-    finalColor = (AmbientColor * DiffuseColor);
+    vec3 color = (AmbientColor * DiffuseColor).rgb + SpecularColor + EmissiveColor;
+
+    // Light seen through a partly dissolved surface is tinted by Tf.
+    color *= mix(transmissionFilter, vec3(1.0), opacity);
+
+    finalColor = vec4(color, opacity);
 }
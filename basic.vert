@@ -3,12 +3,16 @@
 layout(location = 0) in vec3 position;
 layout(location = 1) in vec2 tex_coord;
 layout(location = 2) in vec3 normal;
+layout(location = 3) in vec4 tangent;
 
 uniform mat4 view_proj;
 uniform mat4 model;
+uniform mat3 normalMatrix;
 uniform mat4 light_space_matrix;
 
 out vec3 surfaceNormal;
+out vec3 surfaceTangent;
+out float tangentSign;
 out vec2 fragTexCoord;
 out vec4 worldPos;
 out vec4 fragPosLightSpace;
@@ -16,7 +20,9 @@ out vec4 fragPosLightSpace;
 void main() {
     fragTexCoord = tex_coord;
     worldPos = model * vec4(position, 1);
-    surfaceNormal = (model * vec4(normal, 0.0)).xyz;
+    surfaceNormal = normalMatrix * normal;
+    surfaceTangent = (model * vec4(tangent.xyz, 0.0)).xyz;
+    tangentSign = tangent.w;
     fragPosLightSpace = light_space_matrix * worldPos;
     gl_Position = view_proj * worldPos;
 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+#version 330 core
+
+uniform sampler2D scene;
+
+out vec4 color;
+
+void main() {
+    color = texelFetch(scene, ivec2(gl_FragCoord.xy), 0);
+}
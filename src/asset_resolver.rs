@@ -0,0 +1,126 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Finds files named inside OBJ and MTL statements. Names are resolved against
+/// the directory of the file that referenced them first and then against each
+/// extra search path, so models can be loaded from anywhere.
+pub struct AssetResolver {
+    base_dir: PathBuf,
+    search_paths: Vec<PathBuf>,
+}
+
+impl AssetResolver {
+    pub fn new<P: Into<PathBuf>>(base_dir: P, search_paths: &[PathBuf]) -> Self {
+        Self {
+            base_dir: base_dir.into(),
+            search_paths: search_paths.to_vec(),
+        }
+    }
+
+    /// Resolves `name` to an existing file. Windows-style backslashes are
+    /// treated as separators, and when no exact match exists each path
+    /// component is matched case-insensitively, since exporters rarely agree
+    /// with the file system on either.
+    pub fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let relative = PathBuf::from(name.trim().replace('\\', "/"));
+
+        if relative.is_absolute() {
+            return existing_file(&relative).or_else(|| find_case_insensitive(&relative));
+        }
+
+        std::iter::once(&self.base_dir)
+            .chain(self.search_paths.iter())
+            .find_map(|dir| {
+                let candidate = dir.join(&relative);
+                existing_file(&candidate).or_else(|| find_case_insensitive(&candidate))
+            })
+    }
+}
+
+fn existing_file(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        Some(path.to_path_buf())
+    } else {
+        None
+    }
+}
+
+/// Walks `path` one component at a time, picking the directory entry whose
+/// name matches ignoring ASCII case wherever the exact name is missing.
+fn find_case_insensitive(path: &Path) -> Option<PathBuf> {
+    let mut found = PathBuf::new();
+
+    for component in path.components() {
+        let name = match component {
+            Component::Normal(name) => name,
+            other => {
+                found.push(other);
+                continue;
+            }
+        };
+
+        let exact = found.join(name);
+        if exact.exists() {
+            found = exact;
+            continue;
+        }
+
+        let dir = if found.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            found.as_path()
+        };
+        let name = name.to_str()?;
+        let entry = std::fs::read_dir(dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .eq_ignore_ascii_case(name)
+            })?;
+        found.push(entry.file_name());
+    }
+
+    existing_file(&found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir_with;
+
+    #[test]
+    fn resolves_relative_to_base_dir() {
+        let dir = scratch_dir_with("base_dir", &[("textures/brick.png", b"")]);
+        let resolver = AssetResolver::new(&dir, &[]);
+
+        assert_eq!(
+            resolver.resolve("textures/brick.png"),
+            Some(dir.join("textures/brick.png"))
+        );
+        assert_eq!(resolver.resolve("textures/missing.png"), None);
+    }
+
+    #[test]
+    fn falls_back_to_search_paths() {
+        let dir = scratch_dir_with("search_paths", &[("shared/brick.png", b"")]);
+        let resolver = AssetResolver::new(dir.join("model"), &[dir.join("shared")]);
+
+        assert_eq!(
+            resolver.resolve("brick.png"),
+            Some(dir.join("shared/brick.png"))
+        );
+    }
+
+    #[test]
+    fn matches_backslashes_and_case_insensitively() {
+        let dir = scratch_dir_with("case_insensitive", &[("textures/Sponza_Floor.TGA", b"")]);
+        let resolver = AssetResolver::new(&dir, &[]);
+
+        assert_eq!(
+            resolver.resolve("Textures\\sponza_floor.tga"),
+            Some(dir.join("textures/Sponza_Floor.TGA"))
+        );
+    }
+}
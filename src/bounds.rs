@@ -0,0 +1,281 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Transform, Vector3, Vector4};
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// The smallest box holding every point; a degenerate box at the origin
+    /// when there are none.
+    pub fn from_points<I: IntoIterator<Item = Point3<f32>>>(points: I) -> Self {
+        let mut points = points.into_iter();
+        let first = points.next().unwrap_or_else(Point3::origin);
+        points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |bounds, point| {
+                bounds.union(&Self {
+                    min: point,
+                    max: point,
+                })
+            },
+        )
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    /// The box holding this one after `transform`, which is generally looser
+    /// than the box of the transformed geometry.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Self {
+        Self::from_points(
+            self.get_corners()
+                .map(|corner| transform.transform_point(corner)),
+        )
+    }
+
+    pub fn get_corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    pub fn get_center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn get_size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// A sphere centered on the points' bounding box, just large enough to
+    /// hold them all. Not minimal, but close for typical meshes and cheap.
+    pub fn from_points<I>(points: I) -> Self
+    where
+        I: IntoIterator<Item = Point3<f32>>,
+        I::IntoIter: Clone,
+    {
+        let points = points.into_iter();
+        let center = Aabb::from_points(points.clone()).get_center();
+        let radius = points
+            .map(|point| (point - center).magnitude())
+            .fold(0.0, f32::max);
+        Self { center, radius }
+    }
+
+    /// The smallest sphere holding both spheres.
+    pub fn union(&self, other: &Self) -> Self {
+        let offset = other.center - self.center;
+        let distance = offset.magnitude();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        Self {
+            center: self.center + offset * ((radius - self.radius) / distance),
+            radius,
+        }
+    }
+
+    /// The sphere holding this one after `transform`; non-uniform scale grows
+    /// the radius by the largest axis scale.
+    pub fn transformed(&self, transform: &Matrix4<f32>) -> Self {
+        let scale = [transform.x, transform.y, transform.z]
+            .iter()
+            .map(|axis| axis.truncate().magnitude())
+            .fold(0.0, f32::max);
+        Self {
+            center: transform.transform_point(self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// The six clip planes of a view-projection matrix, pointing inwards.
+pub struct Frustum {
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes of an OpenGL-style (-w..w depth) clip space.
+    pub fn from_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|row| view_proj.row(row));
+        Self::from_planes([w + x, w - x, w + y, w - y, w + z, w - z])
+    }
+
+    /// Extracts the planes of a reverse-Z (0..w depth, near at w) clip
+    /// space. An infinite far plane culls nothing.
+    pub fn from_reverse_z_matrix(view_proj: &Matrix4<f32>) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|row| view_proj.row(row));
+        Self::from_planes([w + x, w - x, w + y, w - y, w - z, z])
+    }
+
+    fn from_planes(planes: [Vector4<f32>; 6]) -> Self {
+        let planes = planes.map(|plane| {
+            let length = plane.truncate().magnitude();
+            if length < 1e-6 {
+                // A plane at infinity; everything is in front of it.
+                Vector4::new(0.0, 0.0, 0.0, 1.0)
+            } else {
+                plane / length
+            }
+        });
+        Self { planes }
+    }
+
+    /// Whether any part of `sphere` may be visible.
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(sphere.center.to_vec()) + plane.w >= -sphere.radius)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> Vec<Point3<f32>> {
+        vec![
+            Point3::new(1.0, -2.0, 0.0),
+            Point3::new(3.0, 2.0, 1.0),
+            Point3::new(2.0, 0.0, -1.0),
+        ]
+    }
+
+    #[test]
+    fn aabb_holds_every_point() {
+        let bounds = Aabb::from_points(points());
+
+        assert_eq!(bounds.min, Point3::new(1.0, -2.0, -1.0));
+        assert_eq!(bounds.max, Point3::new(3.0, 2.0, 1.0));
+        assert_eq!(bounds.get_center(), Point3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn transformed_aabb_follows_rotation_and_translation() {
+        let transform = Matrix4::from_translation(Vector3::new(0.0, 10.0, 0.0))
+            * Matrix4::from_angle_z(cgmath::Deg(90.0));
+        let bounds = Aabb::from_points(points()).transformed(&transform);
+
+        let expected = Aabb {
+            min: Point3::new(-2.0, 11.0, -1.0),
+            max: Point3::new(2.0, 13.0, 1.0),
+        };
+        for (actual, expected) in bounds.get_corners().iter().zip(expected.get_corners()) {
+            assert!((actual - expected).magnitude() < 1e-5, "{:?}", bounds);
+        }
+    }
+
+    #[test]
+    fn sphere_holds_every_point() {
+        let sphere = BoundingSphere::from_points(points());
+
+        for point in points() {
+            assert!((point - sphere.center).magnitude() <= sphere.radius + 1e-5);
+        }
+
+        let scaled = sphere.transformed(&Matrix4::from_nonuniform_scale(1.0, 3.0, 1.0));
+        assert!((scaled.radius - sphere.radius * 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_union_holds_both() {
+        let a = BoundingSphere {
+            center: Point3::new(0.0, 0.0, 0.0),
+            radius: 1.0,
+        };
+        let b = BoundingSphere {
+            center: Point3::new(4.0, 0.0, 0.0),
+            radius: 1.0,
+        };
+
+        assert_eq!(
+            a.union(&b),
+            BoundingSphere {
+                center: Point3::new(2.0, 0.0, 0.0),
+                radius: 3.0,
+            }
+        );
+        let inner = BoundingSphere {
+            center: Point3::new(0.5, 0.0, 0.0),
+            radius: 0.25,
+        };
+        assert_eq!(a.union(&inner), a);
+    }
+
+    #[test]
+    fn frustum_culls_spheres_outside_the_view() {
+        let view = Matrix4::look_to_rh(
+            Point3::new(0.0, 0.0, 0.0),
+            -Vector3::unit_z(),
+            Vector3::unit_y(),
+        );
+        let projection = cgmath::perspective(cgmath::Deg(90.0), 1.0, 0.1, 100.0);
+        let frustum = Frustum::from_matrix(&(projection * view));
+        let sphere = |x, z| BoundingSphere {
+            center: Point3::new(x, 0.0, z),
+            radius: 1.0,
+        };
+
+        assert!(frustum.intersects_sphere(&sphere(0.0, -10.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 10.0)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, -200.0)));
+        assert!(!frustum.intersects_sphere(&sphere(20.0, -10.0)));
+        // Straddling the left plane.
+        assert!(frustum.intersects_sphere(&sphere(-10.5, -10.0)));
+    }
+
+    #[test]
+    fn reverse_z_frustum_has_no_far_plane() {
+        let mut projection = crate::camera::Projection::new(1, 1, cgmath::Deg(90.0), 0.1, 100.0);
+        projection.set_reverse_z(true);
+        let frustum = Frustum::from_reverse_z_matrix(&projection.calc_matrix());
+        let sphere = |x, z| BoundingSphere {
+            center: Point3::new(x, 0.0, z),
+            radius: 1.0,
+        };
+
+        assert!(frustum.intersects_sphere(&sphere(0.0, -10.0)));
+        assert!(frustum.intersects_sphere(&sphere(0.0, -1e6)));
+        assert!(!frustum.intersects_sphere(&sphere(0.0, 10.0)));
+        assert!(!frustum.intersects_sphere(&sphere(20.0, -10.0)));
+    }
+}
@@ -4,7 +4,33 @@ use glium::glutin::event::*;
 use std::f32::consts::FRAC_PI_2;
 use std::time::Duration;
 
-const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+pub const SAFE_FRAC_PI_2: f32 = FRAC_PI_2 - 0.0001;
+
+/// Closest the orbit camera gets to its focus.
+const MIN_ORBIT_DISTANCE: f32 = 0.01;
+
+/// How much one scroll step scales the orbit distance.
+const DOLLY_FACTOR: f32 = 1.2;
+
+/// Narrowest and widest vertical field of view.
+const MIN_FOVY: Deg<f32> = Deg(1.0);
+const MAX_FOVY: Deg<f32> = Deg(120.0);
+
+/// Closest the near clip plane gets to the camera.
+const MIN_ZNEAR: f32 = 0.001;
+
+/// Largest ratio of the near to the far clip distance, so the planes never
+/// meet.
+const CLIP_PLANE_RATIO: f32 = 0.999;
+
+/// How much one scroll step scales the field of view when zooming.
+const ZOOM_FACTOR: f32 = 1.1;
+
+/// Smallest view height of the orthographic projection.
+const MIN_ORTHOGRAPHIC_HEIGHT: f32 = 0.01;
+
+/// Focus movement per pixel of mouse motion, relative to the orbit distance.
+const PAN_PER_PIXEL: f32 = 0.001;
 
 #[derive(Debug)]
 pub struct Camera {
@@ -15,6 +41,7 @@ pub struct Camera {
 }
 
 impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new<V: Into<Point3<f32>>, Y: Into<Rad<f32>>, P: Into<Rad<f32>>>(
         position: V,
         yaw: Y,
@@ -35,14 +62,38 @@ impl Camera {
     }
 
     pub fn get_view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_to_rh(self.position, self.get_forward(), Vector3::unit_y())
+    }
+
+    /// Unit vector the camera looks along.
+    pub fn get_forward(&self) -> Vector3<f32> {
         let (sin_pitch, cos_pitch) = self.pitch.0.sin_cos();
         let (sin_yaw, cos_yaw) = self.yaw.0.sin_cos();
+        Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize()
+    }
+
+    pub fn get_yaw(&self) -> Rad<f32> {
+        self.yaw
+    }
+
+    pub fn get_pitch(&self) -> Rad<f32> {
+        self.pitch
+    }
 
-        Matrix4::look_to_rh(
-            self.position,
-            Vector3::new(cos_pitch * cos_yaw, sin_pitch, cos_pitch * sin_yaw).normalize(),
-            Vector3::unit_y(),
-        )
+    pub fn set_orientation(&mut self, yaw: Rad<f32>, pitch: Rad<f32>) {
+        self.yaw = yaw;
+        self.pitch = pitch;
+    }
+
+    /// Turns the camera towards `target` without moving it.
+    pub fn look_at(&mut self, target: Point3<f32>) {
+        let direction = target - self.position;
+        if direction.magnitude2() == 0.0 {
+            return;
+        }
+        let direction = direction.normalize();
+        self.yaw = Rad(direction.z.atan2(direction.x));
+        self.pitch = Rad(direction.y.asin().clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2));
     }
 
     pub fn get_projection_matrix(&self) -> Matrix4<f32> {
@@ -53,6 +104,14 @@ impl Camera {
         self.projection.resize(width, height)
     }
 
+    pub fn get_projection(&self) -> &Projection {
+        &self.projection
+    }
+
+    pub fn get_projection_mut(&mut self) -> &mut Projection {
+        &mut self.projection
+    }
+
     pub fn get_view_position(&self) -> &Point3<f32> {
         &self.position
     }
@@ -60,41 +119,226 @@ impl Camera {
     pub fn get_aspect_ratio(&self) -> &f32 {
         self.projection.get_aspect_ratio()
     }
+
+    /// Looks at `target` along the preset's axis from outside a sphere of
+    /// `radius`, switching to an orthographic view just taller than it and
+    /// pushing the far plane back to take it in.
+    pub fn set_view_preset(&mut self, preset: ViewPreset, target: Point3<f32>, radius: f32) {
+        let (yaw, pitch) = match preset {
+            ViewPreset::Top => (Rad::from(Deg(-90.0)), Rad(-SAFE_FRAC_PI_2)),
+            ViewPreset::Front => (Rad::from(Deg(-90.0)), Rad(0.0)),
+            ViewPreset::Side => (Rad::from(Deg(180.0)), Rad(0.0)),
+        };
+        self.set_orientation(yaw, pitch);
+        self.position = target - self.get_forward() * 2.0 * radius;
+        self.projection.set_mode(ProjectionMode::Orthographic);
+        self.projection.set_orthographic_height(2.2 * radius);
+        // The far side of the sphere has to stay in front of the far plane.
+        let zfar = self.projection.get_zfar().max(4.0 * radius);
+        self.projection.set_zfar(zfar);
+    }
+}
+
+/// Axis-aligned views for inspecting a scene in plan and elevation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ViewPreset {
+    /// Looking down -Y, with -Z up the screen.
+    Top,
+    /// Looking along -Z.
+    Front,
+    /// Looking along -X.
+    Side,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ProjectionMode {
+    Perspective,
+    /// Parallel projection of a box `orthographic_height` tall, centered on
+    /// the view direction.
+    Orthographic,
 }
 
 #[derive(Debug)]
 pub struct Projection {
+    mode: ProjectionMode,
     aspect: f32,
     fovy: Rad<f32>,
+    orthographic_height: f32,
     znear: f32,
     zfar: f32,
+    reverse_z: bool,
 }
 
 impl Projection {
     pub fn new<F: Into<Rad<f32>>>(width: u32, height: u32, fovy: F, znear: f32, zfar: f32) -> Self {
-        Self {
+        let mut projection = Self {
+            mode: ProjectionMode::Perspective,
             aspect: width as f32 / height as f32,
-            fovy: fovy.into(),
+            fovy: Rad(0.0),
+            orthographic_height: 10.0,
             znear,
             zfar,
-        }
+            reverse_z: false,
+        };
+        projection.set_fovy(fovy);
+        projection
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
     }
 
+    /// The projection to OpenGL's -w..w clip depth, or with reverse-Z to a
+    /// 0..w depth running from 1 at the near plane to 0 at the far one. The
+    /// reverse-Z perspective has no far plane at all.
     pub fn calc_matrix(&self) -> Matrix4<f32> {
-        perspective(self.fovy, self.aspect, self.znear, self.zfar)
+        if !self.reverse_z {
+            return self.calc_standard_matrix();
+        }
+        match self.mode {
+            ProjectionMode::Perspective => {
+                let f = 1.0 / (self.fovy.0 * 0.5).tan();
+                #[rustfmt::skip]
+                let matrix = Matrix4::new(
+                    f / self.aspect, 0.0, 0.0, 0.0,
+                    0.0, f, 0.0, 0.0,
+                    0.0, 0.0, 0.0, -1.0,
+                    0.0, 0.0, self.znear, 0.0,
+                );
+                matrix
+            }
+            ProjectionMode::Orthographic => {
+                let mut matrix = self.calc_standard_matrix();
+                let depth = self.zfar - self.znear;
+                matrix.z.z = 1.0 / depth;
+                matrix.w.z = self.zfar / depth;
+                matrix
+            }
+        }
+    }
+
+    /// The projection to OpenGL's -w..w clip depth between the near and far
+    /// planes, whether or not reverse-Z is on, for renderers that can't do
+    /// it.
+    pub fn calc_standard_matrix(&self) -> Matrix4<f32> {
+        match self.mode {
+            ProjectionMode::Perspective => {
+                perspective(self.fovy, self.aspect, self.znear, self.zfar)
+            }
+            ProjectionMode::Orthographic => {
+                let half_height = self.orthographic_height * 0.5;
+                let half_width = half_height * self.aspect;
+                ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.znear,
+                    self.zfar,
+                )
+            }
+        }
+    }
+
+    pub fn is_reverse_z(&self) -> bool {
+        self.reverse_z
+    }
+
+    /// Switches to reverse-Z depth, which keeps precision far away but needs
+    /// a renderer set up for it; see `calc_matrix`.
+    pub fn set_reverse_z(&mut self, reverse_z: bool) {
+        self.reverse_z = reverse_z;
+    }
+
+    pub fn get_mode(&self) -> ProjectionMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ProjectionMode) {
+        self.mode = mode;
+    }
+
+    /// Height of the orthographic view volume, in world units.
+    pub fn get_orthographic_height(&self) -> f32 {
+        self.orthographic_height
+    }
+
+    pub fn set_orthographic_height(&mut self, height: f32) {
+        self.orthographic_height = height.max(MIN_ORTHOGRAPHIC_HEIGHT);
     }
 
     pub fn get_aspect_ratio(&self) -> &f32 {
         &self.aspect
     }
+
+    /// Vertical field of view.
+    pub fn get_fovy(&self) -> Rad<f32> {
+        self.fovy
+    }
+
+    /// Sets the vertical field of view, kept between 1 and 120 degrees.
+    pub fn set_fovy<F: Into<Rad<f32>>>(&mut self, fovy: F) {
+        let fovy: Rad<f32> = fovy.into();
+        self.fovy = Rad(fovy.0.clamp(Rad::from(MIN_FOVY).0, Rad::from(MAX_FOVY).0));
+    }
+
+    pub fn get_znear(&self) -> f32 {
+        self.znear
+    }
+
+    /// Sets the near clip distance, kept positive and below the far one.
+    pub fn set_znear(&mut self, znear: f32) {
+        self.znear = znear.min(self.zfar * CLIP_PLANE_RATIO).max(MIN_ZNEAR);
+    }
+
+    pub fn get_zfar(&self) -> f32 {
+        self.zfar
+    }
+
+    /// Sets the far clip distance, kept beyond the near one.
+    pub fn set_zfar(&mut self, zfar: f32) {
+        self.zfar = zfar.max(self.znear / CLIP_PLANE_RATIO);
+    }
+
+    /// Narrows the view for positive `steps`, widens it for negative ones:
+    /// the field of view in perspective, the view height in orthographic.
+    fn zoom(&mut self, steps: f32) {
+        let scale = ZOOM_FACTOR.powf(-steps);
+        match self.mode {
+            ProjectionMode::Perspective => self.set_fovy(self.fovy * scale),
+            ProjectionMode::Orthographic => {
+                self.set_orthographic_height(self.orthographic_height * scale)
+            }
+        }
+    }
+}
+
+/// What the scroll wheel does.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScrollMode {
+    /// Moves the camera: along the view when flying, towards the focus when
+    /// orbiting.
+    Move,
+    /// Zooms by changing the field of view, leaving the camera in place.
+    /// Orthographic views always zoom, since moving doesn't change them.
+    Zoom,
+}
+
+/// How `CameraController` moves the camera.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CameraMode {
+    /// WASD to move, mouse to look around, scroll to move along the view.
+    FreeFly,
+    /// Mouse to rotate around the focus point, pan to move the focus in the
+    /// view plane, scroll to dolly towards or away from it.
+    Orbit,
 }
 
 #[derive(Debug)]
 pub struct CameraController {
+    mode: CameraMode,
+    scroll_mode: ScrollMode,
+    focus: Point3<f32>,
     amount_left: f32,
     amount_right: f32,
     amount_forward: f32,
@@ -103,6 +347,8 @@ pub struct CameraController {
     amount_down: f32,
     rotate_horizontal: f32,
     rotate_vertical: f32,
+    pan_horizontal: f32,
+    pan_vertical: f32,
     scroll: f32,
     speed: f32,
     sensitivity: f32,
@@ -111,6 +357,9 @@ pub struct CameraController {
 impl CameraController {
     pub fn new(speed: f32, sensitivity: f32) -> Self {
         Self {
+            mode: CameraMode::FreeFly,
+            scroll_mode: ScrollMode::Move,
+            focus: Point3::new(0.0, 0.0, 0.0),
             amount_left: 0.0,
             amount_right: 0.0,
             amount_forward: 0.0,
@@ -119,12 +368,41 @@ impl CameraController {
             amount_down: 0.0,
             rotate_horizontal: 0.0,
             rotate_vertical: 0.0,
+            pan_horizontal: 0.0,
+            pan_vertical: 0.0,
             scroll: 0.0,
             speed,
             sensitivity,
         }
     }
 
+    pub fn get_mode(&self) -> CameraMode {
+        self.mode
+    }
+
+    /// Switches modes. Orbiting turns the camera to the focus on the next
+    /// update, keeping its position.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        self.mode = mode;
+    }
+
+    pub fn get_scroll_mode(&self) -> ScrollMode {
+        self.scroll_mode
+    }
+
+    pub fn set_scroll_mode(&mut self, scroll_mode: ScrollMode) {
+        self.scroll_mode = scroll_mode;
+    }
+
+    /// The point the orbit mode rotates around.
+    pub fn get_focus(&self) -> &Point3<f32> {
+        &self.focus
+    }
+
+    pub fn set_focus(&mut self, focus: Point3<f32>) {
+        self.focus = focus;
+    }
+
     pub fn process_keyboard(
         &mut self,
         key: glium::glutin::event::VirtualKeyCode,
@@ -169,6 +447,12 @@ impl CameraController {
         self.rotate_vertical = mouse_dy as f32;
     }
 
+    /// Mouse motion while panning; only the orbit mode uses it.
+    pub fn process_pan(&mut self, mouse_dx: f64, mouse_dy: f64) {
+        self.pan_horizontal += mouse_dx as f32;
+        self.pan_vertical += mouse_dy as f32;
+    }
+
     pub fn process_scroll(&mut self, delta: &MouseScrollDelta) {
         self.scroll = match delta {
             // I'm assuming a line is about 100 pixels
@@ -177,7 +461,35 @@ impl CameraController {
         };
     }
 
+    /// This frame's scroll as at most one step either way. Touchpads report
+    /// many pixels at once, hence the clamp.
+    fn scroll_steps(&self) -> f32 {
+        self.scroll.clamp(-1.0, 1.0)
+    }
+
     pub fn update_camera(&mut self, camera: &mut Camera, dt: Duration) {
+        let orthographic = camera.projection.get_mode() == ProjectionMode::Orthographic;
+        if self.scroll_mode == ScrollMode::Zoom || orthographic {
+            // Scrolling up reports negative amounts and zooms in.
+            camera.projection.zoom(-self.scroll_steps());
+            self.scroll = 0.0;
+        }
+        match self.mode {
+            CameraMode::FreeFly => self.update_free_fly(camera, dt),
+            CameraMode::Orbit => self.update_orbit(camera, dt),
+        }
+
+        // If process_mouse isn't called every frame, these values
+        // will not get set to zero, and the camera will rotate
+        // when moving in a non cardinal direction.
+        self.rotate_horizontal = 0.0;
+        self.rotate_vertical = 0.0;
+        self.pan_horizontal = 0.0;
+        self.pan_vertical = 0.0;
+        self.scroll = 0.0;
+    }
+
+    fn update_free_fly(&mut self, camera: &mut Camera, dt: Duration) {
         let dt = dt.as_secs_f32();
 
         // Move forward/backward and left/right
@@ -195,7 +507,6 @@ impl CameraController {
         let scrollward =
             Vector3::new(pitch_cos * yaw_cos, pitch_sin, pitch_cos * yaw_sin).normalize();
         camera.position += scrollward * self.scroll * self.speed * self.sensitivity * dt;
-        self.scroll = 0.0;
 
         // Move up/down. Since we don't use roll, we can just
         // modify the y coordinate directly.
@@ -204,18 +515,186 @@ impl CameraController {
         // Rotate
         camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
         camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        clamp_pitch(camera);
+    }
 
-        // If process_mouse isn't called every frame, these values
-        // will not get set to zero, and the camera will rotate
-        // when moving in a non cardinal direction.
-        self.rotate_horizontal = 0.0;
-        self.rotate_vertical = 0.0;
+    fn update_orbit(&mut self, camera: &mut Camera, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        let distance = (camera.position - self.focus)
+            .magnitude()
+            .max(MIN_ORBIT_DISTANCE);
+
+        // Dragging right swings the camera left around the focus, so the
+        // scene follows the mouse.
+        camera.look_at(self.focus);
+        camera.yaw += Rad(self.rotate_horizontal) * self.sensitivity * dt;
+        camera.pitch += Rad(-self.rotate_vertical) * self.sensitivity * dt;
+        clamp_pitch(camera);
+
+        // Panning moves the focus against the mouse so the scene follows it,
+        // further when far away.
+        let forward = camera.get_forward();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        self.focus +=
+            (up * self.pan_vertical - right * self.pan_horizontal) * distance * PAN_PER_PIXEL;
 
-        // Keep the camera's angle from going too high/low.
-        if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = -Rad(SAFE_FRAC_PI_2);
-        } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
-            camera.pitch = Rad(SAFE_FRAC_PI_2);
+        // Scrolling scales the distance, so each step feels the same at any
+        // range.
+        let distance = (distance * DOLLY_FACTOR.powf(self.scroll_steps())).max(MIN_ORBIT_DISTANCE);
+        camera.position = self.focus - forward * distance;
+    }
+}
+
+/// Keeps the camera's angle from going too high/low.
+fn clamp_pitch(camera: &mut Camera) {
+    if camera.pitch < -Rad(SAFE_FRAC_PI_2) {
+        camera.pitch = -Rad(SAFE_FRAC_PI_2);
+    } else if camera.pitch > Rad(SAFE_FRAC_PI_2) {
+        camera.pitch = Rad(SAFE_FRAC_PI_2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn orbiting(focus: Point3<f32>) -> (Camera, CameraController) {
+        let camera = Camera::new((0.0, 0.0, 5.0), Deg(0.0), Deg(0.0), 16, 9, 45.0, 0.1, 100.0);
+        let mut controller = CameraController::new(2.0, 0.6);
+        controller.set_focus(focus);
+        controller.set_mode(CameraMode::Orbit);
+        (camera, controller)
+    }
+
+    #[test]
+    fn orbiting_keeps_the_focus_centered_at_the_same_distance() {
+        let focus = Point3::new(1.0, 2.0, 0.0);
+        let (mut camera, mut controller) = orbiting(focus);
+        let distance = (camera.position - focus).magnitude();
+
+        controller.process_mouse(30.0, -20.0);
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+        controller.process_mouse(-5.0, 200.0);
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+
+        let to_focus = focus - camera.position;
+        assert!((to_focus.magnitude() - distance).abs() < 1e-4);
+        assert!((to_focus.normalize() - camera.get_forward()).magnitude() < 1e-4);
+    }
+
+    #[test]
+    fn zooming_changes_the_field_of_view_within_limits() {
+        let (mut camera, mut controller) = orbiting(Point3::new(0.0, 0.0, 0.0));
+        controller.set_scroll_mode(ScrollMode::Zoom);
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+        let position = camera.position;
+
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 2.0));
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+        let fovy = Deg::from(camera.get_projection().get_fovy()).0;
+        assert!((fovy - 45.0 / ZOOM_FACTOR).abs() < 1e-3);
+        assert!((camera.position - position).magnitude() < 1e-5);
+
+        for _ in 0..100 {
+            controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, -2.0));
+            controller.update_camera(&mut camera, Duration::from_millis(16));
         }
+        assert_eq!(camera.get_projection().get_fovy(), Rad::from(MAX_FOVY));
+    }
+
+    #[test]
+    fn clip_planes_stay_positive_and_apart() {
+        let mut projection = Projection::new(800, 600, Deg(45.0), 0.1, 100.0);
+        projection.set_znear(-1.0);
+        assert_eq!(projection.get_znear(), MIN_ZNEAR);
+
+        projection.set_znear(500.0);
+        assert!(projection.get_znear() < projection.get_zfar());
+
+        projection.set_zfar(0.0);
+        assert!(projection.get_zfar() > projection.get_znear());
+    }
+
+    #[test]
+    fn orthographic_presets_frame_the_target() {
+        let mut camera = Camera::new((0.0, 0.0, 5.0), Deg(0.0), Deg(0.0), 2, 1, 45.0, 0.1, 100.0);
+        let target = Point3::new(1.0, 2.0, 3.0);
+        for preset in [ViewPreset::Top, ViewPreset::Front, ViewPreset::Side] {
+            camera.set_view_preset(preset, target, 4.0);
+            assert_eq!(
+                camera.get_projection().get_mode(),
+                ProjectionMode::Orthographic
+            );
+
+            // The target lands in the middle of the screen and a point just
+            // inside the view height at its top edge.
+            let view_proj = camera.get_projection_matrix() * camera.get_view_matrix();
+            let center = view_proj.transform_point(target);
+            assert!(
+                center.x.abs() < 1e-3 && center.y.abs() < 1e-3,
+                "{:?}",
+                preset
+            );
+            assert!((-1.0..1.0).contains(&center.z));
+
+            let up = camera
+                .get_forward()
+                .cross(Vector3::unit_y())
+                .cross(camera.get_forward());
+            let edge = view_proj.transform_point(target + up.normalize() * 2.2 * 2.0);
+            assert!((edge.y.abs() - 1.0).abs() < 1e-3, "{:?} {:?}", preset, edge);
+        }
+    }
+
+    #[test]
+    fn reverse_z_puts_the_near_plane_at_one_and_infinity_at_zero() {
+        let mut projection = Projection::new(16, 9, Deg(45.0), 0.1, 100.0);
+        projection.set_reverse_z(true);
+        let depth = |projection: &Projection, distance: f32| {
+            let clip = projection.calc_matrix() * Vector4::new(0.0, 0.0, -distance, 1.0);
+            clip.z / clip.w
+        };
+
+        assert!((depth(&projection, 0.1) - 1.0).abs() < 1e-6);
+        assert!(depth(&projection, 1000.0) > 0.0);
+        assert!(depth(&projection, 1000.0) < depth(&projection, 100.0));
+        assert!(depth(&projection, 1e9) < 1e-6);
+
+        projection.set_mode(ProjectionMode::Orthographic);
+        assert!((depth(&projection, 0.1) - 1.0).abs() < 1e-6);
+        assert!(depth(&projection, 100.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn standard_matrix_ignores_reverse_z() {
+        let mut projection = Projection::new(16, 9, Deg(45.0), 0.1, 100.0);
+        for mode in [ProjectionMode::Perspective, ProjectionMode::Orthographic] {
+            projection.set_mode(mode);
+            projection.set_reverse_z(false);
+            let standard = projection.calc_matrix();
+            projection.set_reverse_z(true);
+
+            assert_eq!(projection.calc_standard_matrix(), standard);
+            assert_ne!(projection.calc_matrix(), standard);
+        }
+    }
+
+    #[test]
+    fn panning_and_dollying_move_the_focus_and_distance() {
+        let (mut camera, mut controller) = orbiting(Point3::new(0.0, 0.0, 0.0));
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+        assert!((camera.position - Point3::new(0.0, 0.0, 5.0)).magnitude() < 1e-4);
+
+        // Looking down -Z, dragging right moves the focus left.
+        controller.process_pan(100.0, 0.0);
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+        assert!(controller.get_focus().x < 0.0);
+        assert!(controller.get_focus().y.abs() < 1e-5);
+
+        controller.process_scroll(&MouseScrollDelta::LineDelta(0.0, 2.0));
+        controller.update_camera(&mut camera, Duration::from_millis(16));
+        let distance = (camera.position - controller.get_focus()).magnitude();
+        assert!((distance - 5.0 / DOLLY_FACTOR).abs() < 1e-3);
     }
 }
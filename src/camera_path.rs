@@ -0,0 +1,378 @@
+use std::f32::consts::{PI, TAU};
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use cgmath::{Deg, Point3, Rad};
+
+use crate::camera::{Camera, SAFE_FRAC_PI_2};
+
+/// Time one played back frame advances the path by, whatever the real frame
+/// time, so every run renders the same views.
+pub const FRAME_TIME: f32 = 1.0 / 60.0;
+
+/// A recorded camera pose, `time` seconds into the path.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub yaw: Rad<f32>,
+    pub pitch: Rad<f32>,
+}
+
+impl Keyframe {
+    pub fn from_camera(time: f32, camera: &Camera) -> Self {
+        Self {
+            time,
+            position: *camera.get_view_position(),
+            yaw: camera.get_yaw(),
+            pitch: camera.get_pitch(),
+        }
+    }
+
+    /// Moves `camera` to the pose, keeping the pitch short of straight up or
+    /// down like `CameraController` does.
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.position = self.position;
+        let pitch = self.pitch.0.clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2);
+        camera.set_orientation(self.yaw, Rad(pitch));
+    }
+
+    fn values(&self) -> [f32; 5] {
+        let [x, y, z]: [f32; 3] = self.position.into();
+        [x, y, z, self.yaw.0, self.pitch.0]
+    }
+
+    fn from_values(time: f32, [x, y, z, yaw, pitch]: [f32; 5]) -> Self {
+        Self {
+            time,
+            position: Point3::new(x, y, z),
+            yaw: Rad(yaw),
+            pitch: Rad(pitch),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Interpolation {
+    /// Passes through every keyframe at its time.
+    CatmullRom,
+    /// One curve with the keyframes as control points. It only passes
+    /// through the first and last, and the times in between are ignored.
+    Bezier,
+}
+
+impl Interpolation {
+    fn name(self) -> &'static str {
+        match self {
+            Self::CatmullRom => "catmull-rom",
+            Self::Bezier => "bezier",
+        }
+    }
+}
+
+/// Keyframes kept in time order, and how to move between them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraPath {
+    keyframes: Vec<Keyframe>,
+    interpolation: Interpolation,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self {
+            keyframes: vec![],
+            interpolation: Interpolation::CatmullRom,
+        }
+    }
+}
+
+impl CameraPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Replaces the keyframes, as after editing a copy of them.
+    pub fn set_keyframes(&mut self, keyframes: Vec<Keyframe>) {
+        self.keyframes = keyframes;
+        self.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    pub fn add_keyframe(&mut self, keyframe: Keyframe) {
+        let index = self
+            .keyframes
+            .partition_point(|other| other.time <= keyframe.time);
+        self.keyframes.insert(index, keyframe);
+    }
+
+    pub fn get_interpolation(&self) -> Interpolation {
+        self.interpolation
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    /// Time of the last keyframe, or 0 for an empty path.
+    pub fn get_duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// The pose at `time`, held at the ends, or `None` for an empty path.
+    /// Yaw turns the short way between keyframes, and pitch is kept short of
+    /// straight up or down where the spline overshoots.
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let first = self.keyframes.first()?;
+        let last = self.keyframes.last()?;
+        let time = time.clamp(first.time, last.time);
+
+        let mut points: Vec<[f32; 5]> = self.keyframes.iter().map(Keyframe::values).collect();
+        for i in 1..points.len() {
+            let turn = points[i][3] - points[i - 1][3];
+            points[i][3] -= TAU * ((turn + PI) / TAU).floor();
+        }
+
+        let mut values = match self.interpolation {
+            Interpolation::CatmullRom if points.len() == 1 => points[0],
+            Interpolation::CatmullRom => {
+                let segment = self
+                    .keyframes
+                    .partition_point(|keyframe| keyframe.time <= time)
+                    .clamp(1, points.len() - 1)
+                    - 1;
+                let (start, end) = (&self.keyframes[segment], &self.keyframes[segment + 1]);
+                let span = end.time - start.time;
+                let u = if span > 0.0 {
+                    (time - start.time) / span
+                } else {
+                    0.0
+                };
+                let p0 = points[segment.saturating_sub(1)];
+                let p3 = points[(segment + 2).min(points.len() - 1)];
+                catmull_rom(p0, points[segment], points[segment + 1], p3, u)
+            }
+            Interpolation::Bezier => {
+                let span = last.time - first.time;
+                let u = if span > 0.0 {
+                    (time - first.time) / span
+                } else {
+                    0.0
+                };
+                de_casteljau(points, u)
+            }
+        };
+        values[4] = values[4].clamp(-SAFE_FRAC_PI_2, SAFE_FRAC_PI_2);
+        Some(Keyframe::from_values(time, values))
+    }
+
+    /// Writes the path as text: an interpolation line, then one line per
+    /// keyframe of time, position and yaw and pitch in degrees.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::from("# time x y z yaw pitch\n");
+        writeln!(text, "interpolation {}", self.interpolation.name()).unwrap();
+        for keyframe in &self.keyframes {
+            writeln!(
+                text,
+                "{} {} {} {} {} {}",
+                keyframe.time,
+                keyframe.position.x,
+                keyframe.position.y,
+                keyframe.position.z,
+                Deg::from(keyframe.yaw).0,
+                Deg::from(keyframe.pitch).0
+            )
+            .unwrap();
+        }
+        fs::write(path, text)
+    }
+
+    /// Reads a path written by `save`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut camera_path = Self::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, message),
+                )
+            };
+
+            if let Some(name) = line.strip_prefix("interpolation ") {
+                camera_path.interpolation = [Interpolation::CatmullRom, Interpolation::Bezier]
+                    .into_iter()
+                    .find(|interpolation| interpolation.name() == name.trim())
+                    .ok_or_else(|| invalid("unknown interpolation"))?;
+                continue;
+            }
+
+            let values = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| invalid("expected numbers"))?;
+            let [time, x, y, z, yaw, pitch]: [f32; 6] = values
+                .try_into()
+                .map_err(|_| invalid("expected time, position, yaw and pitch"))?;
+            camera_path.add_keyframe(Keyframe {
+                time,
+                position: Point3::new(x, y, z),
+                yaw: Deg(yaw).into(),
+                pitch: Deg(pitch).into(),
+            });
+        }
+        Ok(camera_path)
+    }
+}
+
+/// The uniform Catmull-Rom segment from `p1` to `p2` at `u` in 0..=1.
+fn catmull_rom(p0: [f32; 5], p1: [f32; 5], p2: [f32; 5], p3: [f32; 5], u: f32) -> [f32; 5] {
+    let (u2, u3) = (u * u, u * u * u);
+    std::array::from_fn(|i| {
+        0.5 * (2.0 * p1[i]
+            + (p2[i] - p0[i]) * u
+            + (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * u2
+            + (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * u3)
+    })
+}
+
+/// The Bezier curve with control points `points` at `u` in 0..=1.
+fn de_casteljau(mut points: Vec<[f32; 5]>, u: f32) -> [f32; 5] {
+    while points.len() > 1 {
+        points = points
+            .windows(2)
+            .map(|pair| std::array::from_fn(|i| pair[0][i] + (pair[1][i] - pair[0][i]) * u))
+            .collect();
+    }
+    points[0]
+}
+
+/// Plays a path back one fixed step per frame.
+#[derive(Debug, Default)]
+pub struct Playback {
+    time: f32,
+}
+
+impl Playback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_time(&self) -> f32 {
+        self.time
+    }
+
+    /// Moves `camera` to the current pose and steps forward. Returns `false`
+    /// once past the end, leaving the camera where it was.
+    pub fn advance(&mut self, path: &CameraPath, camera: &mut Camera) -> bool {
+        if self.time > path.get_duration() {
+            return false;
+        }
+        match path.sample(self.time) {
+            Some(keyframe) => keyframe.apply(camera),
+            None => return false,
+        }
+        self.time += FRAME_TIME;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir_with;
+    use cgmath::InnerSpace;
+
+    fn keyframe(time: f32, x: f32, yaw: f32) -> Keyframe {
+        Keyframe {
+            time,
+            position: Point3::new(x, 1.0, 0.0),
+            yaw: Deg(yaw).into(),
+            pitch: Rad(0.0),
+        }
+    }
+
+    fn path() -> CameraPath {
+        let mut path = CameraPath::new();
+        path.add_keyframe(keyframe(2.0, 4.0, -170.0));
+        path.add_keyframe(keyframe(0.0, 0.0, 150.0));
+        path.add_keyframe(keyframe(1.0, 1.0, 170.0));
+        path
+    }
+
+    #[test]
+    fn overshooting_pitch_stays_short_of_vertical() {
+        let mut path = CameraPath::new();
+        for (time, pitch) in [(0.0, 0.0), (1.0, 89.0), (2.0, 89.0), (3.0, 0.0)] {
+            path.add_keyframe(Keyframe {
+                pitch: Deg(pitch).into(),
+                ..keyframe(time, 0.0, 0.0)
+            });
+        }
+
+        let sampled = path.sample(1.5).unwrap();
+        assert!(sampled.pitch.0 <= SAFE_FRAC_PI_2, "{:?}", sampled.pitch);
+
+        let mut camera = Camera::new((0.0, 0.0, 0.0), Deg(0.0), Deg(0.0), 16, 9, 45.0, 0.1, 100.0);
+        Keyframe {
+            pitch: Deg(120.0).into(),
+            ..sampled
+        }
+        .apply(&mut camera);
+        assert_eq!(camera.get_pitch(), Rad(SAFE_FRAC_PI_2));
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_keyframes_and_turns_the_short_way() {
+        let path = path();
+        assert_eq!(path.get_duration(), 2.0);
+        for keyframe in path.get_keyframes() {
+            let sampled = path.sample(keyframe.time).unwrap();
+            assert!((sampled.position - keyframe.position).magnitude() < 1e-5);
+        }
+
+        // Halfway from 170 to -170 degrees is 180, not 0.
+        let yaw = Deg::from(path.sample(1.5).unwrap().yaw).0;
+        assert!((yaw.rem_euclid(360.0) - 180.0).abs() < 5.0, "{}", yaw);
+        assert_eq!(
+            path.sample(-1.0).unwrap().position,
+            Point3::new(0.0, 1.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn bezier_only_passes_through_the_ends() {
+        let mut path = path();
+        path.set_interpolation(Interpolation::Bezier);
+        assert_eq!(path.sample(0.0).unwrap().position.x, 0.0);
+        assert_eq!(path.sample(2.0).unwrap().position.x, 4.0);
+        // (1 - u)^2 * 0 + 2u(1 - u) * 1 + u^2 * 4 at u = 0.5.
+        assert!((path.sample(1.0).unwrap().position.x - 1.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn saved_paths_load_back() {
+        let mut path = path();
+        path.set_interpolation(Interpolation::Bezier);
+        let dir = scratch_dir_with("camera_path", &[]);
+        let file = dir.join("path.txt");
+        path.save(&file).unwrap();
+        let loaded = CameraPath::load(&file).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.get_interpolation(), Interpolation::Bezier);
+        for (loaded, saved) in loaded.get_keyframes().iter().zip(path.get_keyframes()) {
+            assert_eq!(loaded.time, saved.time);
+            assert_eq!(loaded.position, saved.position);
+            assert!((loaded.yaw - saved.yaw).0.abs() < 1e-5);
+        }
+    }
+}
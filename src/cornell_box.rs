@@ -0,0 +1,230 @@
+use cgmath::{Deg, One, Quaternion, Rotation3, Vector3};
+use glium::Display;
+
+use crate::camera::Camera;
+use crate::light::{Light, LightKind};
+use crate::load_error::ModelLoadError;
+use crate::model::Model;
+use crate::model_data::{MaterialData, MeshData};
+use crate::primitives::{self, Primitive};
+use crate::scene::{NodeId, Scene};
+use crate::texture_cache::TextureCache;
+use crate::transform::Transform;
+
+// Wall reflectances of the classic Cornell box.
+const WHITE: [f32; 3] = [0.725, 0.71, 0.68];
+const RED: [f32; 3] = [0.63, 0.065, 0.05];
+const GREEN: [f32; 3] = [0.14, 0.45, 0.091];
+const LIGHT_EMISSION: [f32; 3] = [1.0, 0.85, 0.6];
+
+/// The box spans -1..1 along X and Z and 0..2 along Y, open towards +Z.
+const SIZE: f32 = 2.0;
+
+/// The reference view: centered in front of the open side, looking in along
+/// -Z with the whole box in frame.
+pub fn reference_camera(width: u32, height: u32) -> Camera {
+    Camera::new(
+        (0.0, 1.0, 6.8),
+        Deg(-90.0),
+        Deg(0.0),
+        width,
+        height,
+        19.5,
+        0.1,
+        100.0,
+    )
+}
+
+/// One model of the box: its name, meshes, lights and placement.
+struct Part {
+    name: &'static str,
+    meshes: Vec<MeshData>,
+    lights: Vec<Light>,
+    transform: Transform,
+}
+
+fn diffuse(color: [f32; 3]) -> MaterialData {
+    MaterialData {
+        diffuse_color: color,
+        specular_color: [0.0; 3],
+        ..Default::default()
+    }
+}
+
+fn wall(
+    name: &'static str,
+    color: [f32; 3],
+    translation: [f32; 3],
+    rotation: Quaternion<f32>,
+) -> Part {
+    let plane = Primitive::Plane {
+        width: SIZE,
+        depth: SIZE,
+    };
+    Part {
+        name,
+        meshes: plane.build_mesh(diffuse(color)).into_iter().collect(),
+        lights: vec![],
+        transform: Transform {
+            translation: translation.into(),
+            rotation,
+            ..Default::default()
+        },
+    }
+}
+
+fn block(name: &'static str, size: [f32; 3], translation: [f32; 3], angle: Deg<f32>) -> Part {
+    let cube = Primitive::Cube { size: size.into() };
+    Part {
+        name,
+        meshes: cube.build_mesh(diffuse(WHITE)).into_iter().collect(),
+        lights: vec![],
+        transform: Transform {
+            translation: Vector3::from(translation) + Vector3::unit_y() * size[1] * 0.5,
+            rotation: Quaternion::from_angle_y(angle),
+            ..Default::default()
+        },
+    }
+}
+
+/// The walls, the two blocks and the ceiling light, in box space. Planes face
+/// +Y, so each wall is rotated to face into the box.
+fn parts() -> Vec<Part> {
+    let light_panel = Primitive::Plane {
+        width: 0.47,
+        depth: 0.38,
+    };
+    let light_material = MaterialData {
+        emissive_color: LIGHT_EMISSION,
+        ..diffuse([0.0; 3])
+    };
+
+    vec![
+        wall("Floor", WHITE, [0.0, 0.0, 0.0], Quaternion::one()),
+        wall(
+            "Ceiling",
+            WHITE,
+            [0.0, SIZE, 0.0],
+            Quaternion::from_angle_x(Deg(180.0)),
+        ),
+        wall(
+            "Back wall",
+            WHITE,
+            [0.0, 1.0, -1.0],
+            Quaternion::from_angle_x(Deg(90.0)),
+        ),
+        wall(
+            "Left wall",
+            RED,
+            [-1.0, 1.0, 0.0],
+            Quaternion::from_angle_z(Deg(-90.0)),
+        ),
+        wall(
+            "Right wall",
+            GREEN,
+            [1.0, 1.0, 0.0],
+            Quaternion::from_angle_z(Deg(90.0)),
+        ),
+        block(
+            "Tall block",
+            [0.6, 1.2, 0.6],
+            [-0.33, 0.0, -0.29],
+            Deg(17.0),
+        ),
+        block(
+            "Short block",
+            [0.6, 0.6, 0.6],
+            [0.33, 0.0, 0.37],
+            Deg(-17.0),
+        ),
+        Part {
+            name: "Ceiling light",
+            meshes: light_panel.build_mesh(light_material).into_iter().collect(),
+            // Just in front of the panel, which faces down after the flip.
+            lights: vec![Light::new(
+                LightKind::Point,
+                [0.0, 0.05, 0.0],
+                [0.0, 0.0, 0.0],
+                LIGHT_EMISSION,
+                1.0,
+                None,
+            )],
+            transform: Transform {
+                translation: Vector3::new(0.0, SIZE - 0.001, 0.0),
+                rotation: Quaternion::from_angle_x(Deg(180.0)),
+                ..Default::default()
+            },
+        },
+    ]
+}
+
+/// Uploads the box and adds it to `scene` under a new root node, which is
+/// returned.
+pub fn add_to_scene(
+    scene: &mut Scene,
+    display: &Display,
+    texture_cache: &mut TextureCache,
+) -> Result<NodeId, ModelLoadError> {
+    let root = scene.add_node("Cornell box", None, Transform::default());
+    for part in parts() {
+        let mut data = primitives::model_data(part.name, part.meshes);
+        data.lights = part.lights;
+        let model = Model::upload(display, data, texture_cache)?;
+
+        let node = scene.add_node(part.name, Some(root), part.transform);
+        scene.set_model(node, model);
+    }
+    Ok(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{InnerSpace, Point3, Transform as _};
+
+    #[test]
+    fn walls_face_into_the_box() {
+        let center = Point3::new(0.0, 1.0, 0.0);
+        // The five walls come first.
+        for part in parts().iter().take(5) {
+            let matrix = part.transform.get_matrix();
+            let origin = matrix.transform_point(Point3::new(0.0, 0.0, 0.0));
+            let normal = matrix.transform_vector(Vector3::unit_y());
+            assert!(
+                normal.dot(center - origin) > 0.0,
+                "{} faces away from the box",
+                part.name
+            );
+        }
+    }
+
+    #[test]
+    fn everything_fits_inside_the_box() {
+        for part in parts() {
+            let matrix = part.transform.get_matrix();
+            for mesh in &part.meshes {
+                let bounds = mesh.bounds.transformed(&matrix);
+                for corner in bounds.get_corners() {
+                    assert!(
+                        (-1.0 - 1e-5..=1.0 + 1e-5).contains(&corner.x)
+                            && (-1e-5..=SIZE + 1e-5).contains(&corner.y)
+                            && (-1.0 - 1e-5..=1.0 + 1e-5).contains(&corner.z),
+                        "{} sticks out at {:?}",
+                        part.name,
+                        corner
+                    );
+                }
+            }
+        }
+
+        let light = parts()
+            .into_iter()
+            .find(|part| !part.lights.is_empty())
+            .unwrap();
+        let position = light
+            .transform
+            .get_matrix()
+            .transform_point((*light.lights[0].get_position()).into());
+        assert!(position.y < SIZE && position.y > SIZE - 0.1);
+    }
+}
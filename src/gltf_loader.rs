@@ -0,0 +1,616 @@
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+use rayon::prelude::*;
+
+use crate::light::{Light, LightKind};
+use crate::load_error::ModelLoadError;
+use crate::model_data::{
+    build_indexed_mesh, AlphaMode, MaterialData, MeshData, ModelData, SamplerSettings,
+};
+use crate::model_loader::LoadProgress;
+use crate::texture_cache::{canonical_path, report_texture_failure, ImageData, TextureLoadError};
+use crate::transform;
+
+/// Loads every mesh primitive and punctual light of a glTF 2.0 file's default
+/// scene. Node transforms are baked into the vertices and lights, so
+/// everything returned lives in the file's root space. Primitives are built
+/// and images decoded in parallel, each image on its own so that a broken
+/// one only costs its own texture.
+pub fn load_gltf(
+    path: &str,
+    cached: &HashSet<PathBuf>,
+    progress: &LoadProgress,
+) -> Result<ModelData, ModelLoadError> {
+    let gltf_error = |source| ModelLoadError::Gltf {
+        path: path.into(),
+        source,
+    };
+    let gltf::Gltf { document, blob } = gltf::Gltf::open(path).map_err(gltf_error)?;
+    let base_dir = Path::new(path).parent().unwrap_or_else(|| Path::new("./"));
+    let buffers = gltf::import_buffers(&document, Some(base_dir), blob).map_err(gltf_error)?;
+    let source = canonical_path(Path::new(path));
+
+    let mut primitives = vec![];
+    let mut lights = vec![];
+    if let Some(scene) = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        for node in scene.nodes() {
+            visit_node(&node, Matrix4::identity(), &mut primitives, &mut lights);
+        }
+    }
+
+    let images: Vec<(PathBuf, gltf::Image)> = document
+        .images()
+        .map(|image| (image_key(&source, image.index()), image))
+        .filter(|(key, _)| !cached.contains(key))
+        .collect();
+    progress.add_work(primitives.len() + images.len());
+
+    let (meshes, images) = rayon::join(
+        || {
+            primitives
+                .into_par_iter()
+                .filter_map(|(primitive, transform)| {
+                    let mesh = load_primitive(&primitive, &transform, &buffers, &source);
+                    progress.complete_step();
+                    mesh
+                })
+                .collect()
+        },
+        || {
+            images
+                .into_par_iter()
+                .map(|(key, image)| {
+                    let data =
+                        gltf::image::Data::from_source(image.source(), Some(base_dir), &buffers)
+                            .map(|data| ImageData {
+                                pixels: rgba8_pixels(&data),
+                                width: data.width,
+                                height: data.height,
+                            })
+                            .map_err(|error| {
+                                report_texture_failure(&key, &TextureLoadError::Embedded(error))
+                            })
+                            .ok();
+                    progress.complete_step();
+                    (key, data)
+                })
+                .collect()
+        },
+    );
+
+    // Buffers and images in files of their own are sources too, so editing
+    // them reloads the model.
+    let buffer_uris = document
+        .buffers()
+        .filter_map(|buffer| match buffer.source() {
+            gltf::buffer::Source::Uri(uri) => Some(uri),
+            gltf::buffer::Source::Bin => None,
+        });
+    let image_uris = document.images().filter_map(|image| match image.source() {
+        gltf::image::Source::Uri { uri, .. } => Some(uri),
+        gltf::image::Source::View { .. } => None,
+    });
+    let sources = std::iter::once(source)
+        .chain(
+            buffer_uris
+                .chain(image_uris)
+                .filter_map(|uri| uri_path(base_dir, uri))
+                .map(|path| canonical_path(&path)),
+        )
+        .collect();
+
+    Ok(ModelData {
+        path: path.into(),
+        sources,
+        meshes,
+        images,
+        lights,
+        scale: 1.0,
+    })
+}
+
+/// The file a buffer or image URI names, percent-decoded the way
+/// `gltf::import_buffers` reads it, or `None` for data URIs and other
+/// schemes.
+fn uri_path(base_dir: &Path, uri: &str) -> Option<PathBuf> {
+    if let Some(path) = uri
+        .strip_prefix("file://")
+        .or_else(|| uri.strip_prefix("file:"))
+    {
+        return Some(PathBuf::from(path));
+    }
+    if uri.contains(':') {
+        return None;
+    }
+    let path = urlencoding::decode(uri).ok()?;
+    Some(base_dir.join(&*path))
+}
+
+/// Collects the primitives and lights under `node` with their root-space
+/// transforms.
+fn visit_node<'a>(
+    node: &gltf::Node<'a>,
+    parent_transform: Matrix4<f32>,
+    primitives: &mut Vec<(gltf::Primitive<'a>, Matrix4<f32>)>,
+    lights: &mut Vec<Light>,
+) {
+    let transform = parent_transform * Matrix4::from(node.transform().matrix());
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            primitives.push((primitive, transform));
+        }
+    }
+    if let Some(light) = node.light() {
+        lights.push(convert_light(&light, &transform));
+    }
+    for child in node.children() {
+        visit_node(&child, transform, primitives, lights);
+    }
+}
+
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    transform: &Matrix4<f32>,
+    buffers: &[gltf::buffer::Data],
+    source: &Path,
+) -> Option<MeshData> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let material = primitive.material();
+
+    let positions: Vec<[f32; 3]> = reader.read_positions()?.collect();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+    let set = tex_coord_set(&material, source);
+    let tex_coords = reader.read_tex_coords(set).or_else(|| {
+        if set != 0 {
+            eprintln!(
+                "warning: a primitive in {} has no TEXCOORD_{}, using set 0",
+                source.display(),
+                set
+            );
+        }
+        reader.read_tex_coords(0)
+    });
+    let tex_coords: Option<Vec<[f32; 2]>> =
+        tex_coords.map(|tex_coords| tex_coords.into_f32().collect());
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+
+    let triangles = match triangle_list(primitive.mode(), &indices) {
+        Some(triangles) => triangles,
+        None => {
+            eprintln!(
+                "warning: skipping {:?} primitive in {}",
+                primitive.mode(),
+                source.display()
+            );
+            return None;
+        }
+    };
+
+    let has_normals = normals.is_some();
+    let has_tex_coords = tex_coords.is_some();
+    let data = bake_transform(positions, normals, tex_coords, transform);
+
+    // A mirroring transform turns the triangles inside out.
+    let mirrored = transform.determinant() < 0.0;
+    let polys: Vec<obj::SimplePolygon> = triangles
+        .iter()
+        .map(|&[a, b, c]| {
+            let corners = if mirrored { [a, c, b] } else { [a, b, c] };
+            obj::SimplePolygon(
+                corners
+                    .iter()
+                    .map(|&corner| {
+                        let corner = corner as usize;
+                        obj::IndexTuple(
+                            corner,
+                            has_tex_coords.then_some(corner),
+                            has_normals.then_some(corner),
+                        )
+                    })
+                    .collect(),
+            )
+        })
+        .collect();
+
+    let sampler_settings = material
+        .pbr_metallic_roughness()
+        .base_color_texture()
+        .map_or_else(SamplerSettings::default, |info| {
+            sampler_settings(&info.texture().sampler())
+        });
+    let (vertices, indices) = build_indexed_mesh(&data, &polys);
+
+    MeshData::new(
+        vertices,
+        indices,
+        load_material(&material, source),
+        sampler_settings,
+    )
+}
+
+/// The texture coordinate set the material's textures read. Vertices carry a
+/// single set, so a material whose textures read different ones gets set 0
+/// for all of them, with a warning.
+fn tex_coord_set(material: &gltf::Material, source: &Path) -> u32 {
+    let pbr = material.pbr_metallic_roughness();
+    let sets: BTreeSet<u32> = [
+        pbr.base_color_texture().map(|info| info.tex_coord()),
+        pbr.metallic_roughness_texture()
+            .map(|info| info.tex_coord()),
+        material.normal_texture().map(|normal| normal.tex_coord()),
+        material.emissive_texture().map(|info| info.tex_coord()),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    match sets.iter().collect::<Vec<_>>()[..] {
+        [] => 0,
+        [&set] => set,
+        _ => {
+            eprintln!(
+                "warning: material {} in {} reads several texture coordinate sets, using set 0",
+                material.name().unwrap_or("without a name"),
+                source.display()
+            );
+            0
+        }
+    }
+}
+
+/// Reads a metallic-roughness material. The shader shades these with the
+/// same Blinn-Phong terms as MTL materials, deriving the specular tint and
+/// exponent from metallic and roughness per texel.
+fn load_material(material: &gltf::Material, source: &Path) -> MaterialData {
+    let pbr = material.pbr_metallic_roughness();
+    let [red, green, blue, alpha] = pbr.base_color_factor();
+    let alpha_mode = match material.alpha_mode() {
+        gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+        // glTF's default cutoff.
+        gltf::material::AlphaMode::Mask => AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5)),
+        gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+    };
+    let map = |texture: gltf::Texture| image_key(source, texture.source().index());
+
+    MaterialData {
+        ambient_color: [red, green, blue],
+        diffuse_color: [red, green, blue],
+        specular_color: [1.0; 3],
+        emissive_color: material.emissive_factor(),
+        transmission_filter: [1.0; 3],
+        shininess: 0.0,
+        // glTF's default index of refraction.
+        optical_density: 1.5,
+        dissolve: alpha,
+        alpha_mode,
+        illumination_model: 2,
+        metallic_roughness: Some([pbr.metallic_factor(), pbr.roughness_factor()]),
+        ambient_map: None,
+        diffuse_map: pbr.base_color_texture().map(|info| map(info.texture())),
+        specular_map: None,
+        emissive_map: material.emissive_texture().map(|info| map(info.texture())),
+        shininess_map: None,
+        dissolve_map: None,
+        normal_map: material
+            .normal_texture()
+            .map(|normal| map(normal.texture())),
+        metallic_roughness_map: pbr
+            .metallic_roughness_texture()
+            .map(|info| map(info.texture())),
+    }
+}
+
+/// Images are keyed by their index within the file, which covers both
+/// embedded and external images.
+fn image_key(source: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}#image{}", source.display(), index))
+}
+
+/// The glTF file an image key from `image_key` belongs to, or `None` for the
+/// keys of ordinary image files.
+pub fn image_source(key: &Path) -> Option<PathBuf> {
+    let (source, index) = key.to_str()?.rsplit_once("#image")?;
+    index.parse::<usize>().ok()?;
+    Some(PathBuf::from(source))
+}
+
+/// Expands strips and fans into a plain triangle list. Points and lines
+/// have no surface and yield `None`.
+fn triangle_list(mode: gltf::mesh::Mode, indices: &[u32]) -> Option<Vec<[u32; 3]>> {
+    use gltf::mesh::Mode;
+
+    let triangles = match mode {
+        Mode::Triangles => indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect(),
+        Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+            .map(|i| {
+                // Every other strip triangle is swapped to keep the winding.
+                if i % 2 == 0 {
+                    [indices[i], indices[i + 1], indices[i + 2]]
+                } else {
+                    [indices[i + 1], indices[i], indices[i + 2]]
+                }
+            })
+            .collect(),
+        Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+            .map(|i| [indices[i], indices[i + 1], indices[0]])
+            .collect(),
+        _ => return None,
+    };
+
+    Some(triangles)
+}
+
+/// Moves vertex data into the root space of the file, packing it into the
+/// `obj` crate's layout so the OBJ mesh pipeline can weld it and generate
+/// whatever normals and tangents are missing.
+fn bake_transform(
+    positions: Vec<[f32; 3]>,
+    normals: Option<Vec<[f32; 3]>>,
+    tex_coords: Option<Vec<[f32; 2]>>,
+    transform: &Matrix4<f32>,
+) -> obj::ObjData {
+    let normal_matrix = transform::normal_matrix(transform);
+
+    obj::ObjData {
+        position: positions
+            .into_iter()
+            .map(|[x, y, z]| (transform * Vector4::new(x, y, z, 1.0)).truncate().into())
+            .collect(),
+        texture: tex_coords.unwrap_or_default(),
+        normal: normals
+            .unwrap_or_default()
+            .into_iter()
+            .map(|normal| (normal_matrix * Vector3::from(normal)).normalize().into())
+            .collect(),
+        ..Default::default()
+    }
+}
+
+fn convert_light(light: &gltf::khr_lights_punctual::Light, transform: &Matrix4<f32>) -> Light {
+    use gltf::khr_lights_punctual::Kind;
+
+    let kind = match light.kind() {
+        Kind::Directional => LightKind::Directional,
+        Kind::Point => LightKind::Point,
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => LightKind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        },
+    };
+
+    // Lights sit at their node's origin and shine down its -Z axis.
+    let position = (transform * Vector4::new(0.0, 0.0, 0.0, 1.0)).truncate();
+    let direction = (transform * Vector4::new(0.0, 0.0, -1.0, 0.0))
+        .truncate()
+        .normalize();
+
+    Light::new(
+        kind,
+        position.into(),
+        direction.into(),
+        light.color(),
+        light.intensity(),
+        light.range(),
+    )
+}
+
+fn sampler_settings(sampler: &gltf::texture::Sampler) -> SamplerSettings {
+    use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let defaults = SamplerSettings::default();
+
+    SamplerSettings {
+        wrap: match sampler.wrap_s() {
+            WrappingMode::ClampToEdge => SamplerWrapFunction::Clamp,
+            WrappingMode::MirroredRepeat => SamplerWrapFunction::Mirror,
+            WrappingMode::Repeat => SamplerWrapFunction::Repeat,
+        },
+        minify: sampler
+            .min_filter()
+            .map_or(defaults.minify, |filter| match filter {
+                MinFilter::Nearest => MinifySamplerFilter::Nearest,
+                MinFilter::Linear => MinifySamplerFilter::Linear,
+                MinFilter::NearestMipmapNearest => MinifySamplerFilter::NearestMipmapNearest,
+                MinFilter::LinearMipmapNearest => MinifySamplerFilter::LinearMipmapNearest,
+                MinFilter::NearestMipmapLinear => MinifySamplerFilter::NearestMipmapLinear,
+                MinFilter::LinearMipmapLinear => MinifySamplerFilter::LinearMipmapLinear,
+            }),
+        magnify: sampler
+            .mag_filter()
+            .map_or(defaults.magnify, |filter| match filter {
+                MagFilter::Nearest => MagnifySamplerFilter::Nearest,
+                MagFilter::Linear => MagnifySamplerFilter::Linear,
+            }),
+    }
+}
+
+/// Converts any glTF image format to 8-bit RGBA. Single-channel images are
+/// luminance and two-channel ones luminance plus alpha.
+fn rgba8_pixels(image: &gltf::image::Data) -> Vec<u8> {
+    use gltf::image::Format;
+
+    let (channels, channel_bytes) = match image.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    image
+        .pixels
+        .chunks_exact(channels * channel_bytes)
+        .flat_map(|texel| {
+            let channel = |index: usize| -> u8 {
+                let bytes = &texel[index * channel_bytes..(index + 1) * channel_bytes];
+                match channel_bytes {
+                    1 => bytes[0],
+                    2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+                    _ => {
+                        let value = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                        (value.clamp(0.0, 1.0) * 255.0).round() as u8
+                    }
+                }
+            };
+            match channels {
+                1 => [channel(0), channel(0), channel(0), 255],
+                2 => [channel(0), channel(0), channel(0), channel(1)],
+                3 => [channel(0), channel(1), channel(2), 255],
+                _ => [channel(0), channel(1), channel(2), channel(3)],
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strips_and_fans_become_triangle_lists() {
+        use gltf::mesh::Mode;
+
+        let indices = [0, 1, 2, 3, 4];
+        assert_eq!(
+            triangle_list(Mode::TriangleStrip, &indices),
+            Some(vec![[0, 1, 2], [2, 1, 3], [2, 3, 4]])
+        );
+        assert_eq!(
+            triangle_list(Mode::TriangleFan, &indices),
+            Some(vec![[1, 2, 0], [2, 3, 0], [3, 4, 0]])
+        );
+        assert_eq!(
+            triangle_list(Mode::Triangles, &indices[..3]),
+            Some(vec![[0, 1, 2]])
+        );
+        assert_eq!(triangle_list(Mode::Lines, &indices), None);
+    }
+
+    #[test]
+    fn baked_normals_stay_perpendicular_under_non_uniform_scale() {
+        let transform = Matrix4::from_translation(Vector3::new(1.0, 0.0, 0.0))
+            * Matrix4::from_nonuniform_scale(4.0, 1.0, 1.0);
+        // A slanted plane x + y = 1 with its normal.
+        let normal = Vector3::new(1.0f32, 1.0, 0.0).normalize();
+
+        let data = bake_transform(
+            vec![[1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            Some(vec![normal.into()]),
+            None,
+            &transform,
+        );
+
+        assert_eq!(data.position, vec![[5.0, 0.0, 0.0], [1.0, 1.0, 0.0]]);
+        let edge = Vector3::from(data.position[1]) - Vector3::from(data.position[0]);
+        let baked_normal = Vector3::from(data.normal[0]);
+        assert!(edge.dot(baked_normal).abs() < 1e-5);
+        assert!((baked_normal.magnitude() - 1.0).abs() < 1e-5);
+        assert!(data.texture.is_empty());
+    }
+
+    #[test]
+    fn image_formats_expand_to_rgba8() {
+        let image = |format, pixels: Vec<u8>| gltf::image::Data {
+            pixels,
+            format,
+            width: 1,
+            height: 1,
+        };
+
+        assert_eq!(
+            rgba8_pixels(&image(gltf::image::Format::R8, vec![7])),
+            vec![7, 7, 7, 255]
+        );
+        assert_eq!(
+            rgba8_pixels(&image(gltf::image::Format::R8G8, vec![7, 9])),
+            vec![7, 7, 7, 9]
+        );
+        let sixteen_bit: Vec<u8> = [0xff00u16, 0x1200, 0x3400]
+            .iter()
+            .flat_map(|channel| channel.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            rgba8_pixels(&image(gltf::image::Format::R16G16B16, sixteen_bit)),
+            vec![0xff, 0x12, 0x34, 255]
+        );
+        let float: Vec<u8> = [1.5f32, 0.0, 0.5, 1.0]
+            .iter()
+            .flat_map(|channel| channel.to_ne_bytes())
+            .collect();
+        assert_eq!(
+            rgba8_pixels(&image(gltf::image::Format::R32G32B32A32FLOAT, float)),
+            vec![255, 0, 128, 255]
+        );
+    }
+
+    #[test]
+    fn textures_pick_their_texture_coordinate_set() {
+        let gltf = br#"{
+            "asset": { "version": "2.0" },
+            "images": [{ "uri": "a.png" }],
+            "textures": [{ "source": 0 }],
+            "materials": [
+                {
+                    "pbrMetallicRoughness": { "baseColorTexture": { "index": 0, "texCoord": 1 } },
+                    "normalTexture": { "index": 0, "texCoord": 1 }
+                },
+                {
+                    "pbrMetallicRoughness": { "baseColorTexture": { "index": 0, "texCoord": 1 } },
+                    "emissiveTexture": { "index": 0 }
+                },
+                {}
+            ]
+        }"#;
+        let document = gltf::Gltf::from_slice(gltf).unwrap().document;
+        let sets: Vec<u32> = document
+            .materials()
+            .map(|material| tex_coord_set(&material, Path::new("scene.gltf")))
+            .collect();
+
+        assert_eq!(sets, vec![1, 0, 0]);
+    }
+
+    #[test]
+    fn uris_are_percent_decoded() {
+        let base_dir = Path::new("/models");
+        assert_eq!(
+            uri_path(base_dir, "my%20tex.png"),
+            Some(PathBuf::from("/models/my tex.png"))
+        );
+        assert_eq!(
+            uri_path(base_dir, "file:///textures/a.png"),
+            Some(PathBuf::from("/textures/a.png"))
+        );
+        assert_eq!(uri_path(base_dir, "data:image/png;base64,AAAA"), None);
+    }
+
+    #[test]
+    fn image_keys_map_back_to_their_source() {
+        let source = Path::new("/models/scene.gltf");
+        assert_eq!(
+            image_source(&image_key(source, 3)),
+            Some(source.to_path_buf())
+        );
+        assert_eq!(image_source(Path::new("/textures/brick.png")), None);
+    }
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+use crate::texture_cache::canonical_path;
+
+/// How long a file has to stay untouched before it is reported, so an editor
+/// writing it in several steps triggers a single reload.
+const SETTLE_TIME: Duration = Duration::from_millis(200);
+
+/// Watches asset files and reports the ones that were written. Directories
+/// are watched rather than the files themselves, since many editors save by
+/// replacing the file.
+pub struct AssetWatcher {
+    watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+    files: HashSet<PathBuf>,
+    directories: HashSet<PathBuf>,
+    changed: HashMap<PathBuf, Instant>,
+}
+
+impl AssetWatcher {
+    pub fn new() -> notify::Result<Self> {
+        let (sender, events) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event| {
+            // The receiver only goes away with the watcher.
+            let _ = sender.send(event);
+        })?;
+        Ok(Self {
+            watcher,
+            events,
+            files: HashSet::new(),
+            directories: HashSet::new(),
+            changed: HashMap::new(),
+        })
+    }
+
+    /// Starts reporting changes to the file at `path`. Files that don't
+    /// exist, such as generated models' names, are ignored.
+    pub fn watch(&mut self, path: &Path) {
+        let path = canonical_path(path);
+        if !path.is_file() || self.files.contains(&path) {
+            return;
+        }
+        let directory = match path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory.to_owned(),
+            _ => return,
+        };
+        if !self.directories.contains(&directory) {
+            if let Err(error) = self.watcher.watch(&directory, RecursiveMode::NonRecursive) {
+                eprintln!(
+                    "warning: can't watch {} for changes: {}",
+                    directory.display(),
+                    error
+                );
+                return;
+            }
+            self.directories.insert(directory);
+        }
+        self.files.insert(path);
+    }
+
+    /// Canonical paths of the watched files that were written and have since
+    /// settled. Call once per frame.
+    pub fn take_changed(&mut self) -> Vec<PathBuf> {
+        let now = Instant::now();
+        for event in self.events.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    eprintln!("warning: file watcher error: {}", error);
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                let path = canonical_path(&path);
+                if self.files.contains(&path) {
+                    self.changed.insert(path, now);
+                }
+            }
+        }
+
+        let settled: Vec<PathBuf> = self
+            .changed
+            .iter()
+            .filter(|(_, &time)| now.duration_since(time) >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.changed.remove(path);
+        }
+        settled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir_with;
+    use std::fs;
+
+    #[test]
+    fn reports_written_files_once_they_settle() {
+        let directory = scratch_dir_with(
+            "hot_reload",
+            &[("watched.mtl", b"newmtl a\n"), ("other.mtl", b"newmtl b\n")],
+        );
+        let watched = directory.join("watched.mtl");
+        let other = directory.join("other.mtl");
+
+        let mut watcher = AssetWatcher::new().unwrap();
+        watcher.watch(&watched);
+        watcher.watch(&directory.join("missing.mtl"));
+        assert!(watcher.take_changed().is_empty());
+
+        fs::write(&watched, "newmtl c\n").unwrap();
+        fs::write(&other, "newmtl d\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut changed = vec![];
+        while changed.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(50));
+            changed = watcher.take_changed();
+        }
+        assert_eq!(changed, vec![canonical_path(&watched)]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
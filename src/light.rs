@@ -0,0 +1,69 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    Spot {
+        inner_cone_angle: f32,
+        outer_cone_angle: f32,
+    },
+}
+
+/// A punctual light as described by KHR_lights_punctual, placed in model
+/// space.
+#[derive(Copy, Clone, Debug)]
+pub struct Light {
+    kind: LightKind,
+    position: [f32; 3],
+    direction: [f32; 3],
+    color: [f32; 3],
+    intensity: f32,
+    range: Option<f32>,
+}
+
+impl Light {
+    pub fn new(
+        kind: LightKind,
+        position: [f32; 3],
+        direction: [f32; 3],
+        color: [f32; 3],
+        intensity: f32,
+        range: Option<f32>,
+    ) -> Self {
+        Self {
+            kind,
+            position,
+            direction,
+            color,
+            intensity,
+            range,
+        }
+    }
+
+    pub fn get_kind(&self) -> &LightKind {
+        &self.kind
+    }
+
+    pub fn get_position(&self) -> &[f32; 3] {
+        &self.position
+    }
+
+    /// The direction light travels in; unused by point lights.
+    pub fn get_direction(&self) -> &[f32; 3] {
+        &self.direction
+    }
+
+    pub fn get_color(&self) -> &[f32; 3] {
+        &self.color
+    }
+
+    /// Candela for point and spot lights, lux for directional lights.
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    /// Distance at which the light's influence may be cut off; `None` means
+    /// unlimited.
+    pub fn get_range(&self) -> Option<f32> {
+        self.range
+    }
+}
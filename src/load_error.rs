@@ -0,0 +1,120 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Why a model failed to load. Every variant names the file at fault.
+#[derive(Debug)]
+pub enum ModelLoadError {
+    /// The OBJ file is missing or malformed.
+    Obj {
+        path: PathBuf,
+        source: obj::ObjError,
+    },
+    /// A material library named by an OBJ is missing or malformed.
+    MaterialLibrary {
+        path: PathBuf,
+        library: String,
+        source: obj::MtlError,
+    },
+    /// The glTF file or one of its buffers is missing or malformed.
+    Gltf { path: PathBuf, source: gltf::Error },
+    /// The background thread loading the model died before finishing.
+    LoaderPanicked { path: PathBuf },
+    /// A mesh's vertex or index buffer couldn't be created on the GPU.
+    Upload {
+        path: PathBuf,
+        source: BufferUploadError,
+    },
+}
+
+impl fmt::Display for ModelLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Obj { path, source } => {
+                write!(f, "failed to load {}: {}", path.display(), source)
+            }
+            Self::MaterialLibrary {
+                path,
+                library,
+                source,
+            } => write!(
+                f,
+                "failed to load material library {} of {}: {}",
+                library,
+                path.display(),
+                source
+            ),
+            Self::Gltf { path, source } => {
+                write!(f, "failed to load {}: {}", path.display(), source)
+            }
+            Self::LoaderPanicked { path } => {
+                write!(f, "the loader for {} panicked", path.display())
+            }
+            Self::Upload { path, source } => {
+                write!(
+                    f,
+                    "failed to upload a mesh of {}: {}",
+                    path.display(),
+                    source
+                )
+            }
+        }
+    }
+}
+
+impl Error for ModelLoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Obj { source, .. } => Some(source),
+            Self::MaterialLibrary { source, .. } => Some(source),
+            Self::Gltf { source, .. } => Some(source),
+            Self::LoaderPanicked { .. } => None,
+            Self::Upload { source, .. } => Some(source),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BufferUploadError {
+    Vertices(glium::vertex::BufferCreationError),
+    Indices(glium::index::BufferCreationError),
+}
+
+impl fmt::Display for BufferUploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Vertices(source) => write!(f, "vertex buffer: {}", source),
+            Self::Indices(source) => write!(f, "index buffer: {}", source),
+        }
+    }
+}
+
+impl Error for BufferUploadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Vertices(source) => Some(source),
+            Self::Indices(source) => Some(source),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_name_the_file_and_cause() {
+        let error = ModelLoadError::Obj {
+            path: PathBuf::from("models/missing.obj"),
+            source: obj::ObjError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no such file",
+            )),
+        };
+
+        let message = error.to_string();
+        assert!(message.contains("models/missing.obj"), "{}", message);
+        assert!(message.contains("no such file"), "{}", message);
+        assert!(error.source().is_some());
+    }
+}
@@ -1,28 +1,103 @@
 #[macro_use]
 extern crate glium;
 
+mod asset_resolver;
+mod bounds;
 mod camera;
+mod camera_path;
+mod cornell_box;
+mod gltf_loader;
+mod hot_reload;
+mod light;
+mod load_error;
+mod mesh_cache;
 mod model;
+mod model_data;
+mod model_loader;
 mod model_render_system;
+mod primitives;
 mod renderer;
+mod scene;
 mod shadow_render_system;
+#[cfg(test)]
+mod test_util;
+mod texture_cache;
+mod transform;
 
+use cgmath::InnerSpace;
+use light::{Light, LightKind};
 use model::Model;
+use model_data::MaterialData;
+use model_loader::PendingModel;
 use pollster::FutureExt;
+use primitives::Primitive;
+use scene::{NodeId, Scene};
+use transform::Transform;
 
 const WIDTH: u32 = 1920;
 const HEIGHT: u32 = 1200;
 
+/// Color of the animated light used when no loaded model brings its own.
+const DEFAULT_LIGHT_COLOR: [f32; 3] = [1.0, 0.9, 0.66];
+
+/// Where the camera path editor saves and loads, relative to the working
+/// directory.
+const CAMERA_PATH_FILE: &str = "camera_path.txt";
+
+/// How far back along its direction a directional light's shadow camera sits.
+const DIRECTIONAL_LIGHT_DISTANCE: f32 = 15.0;
+
+/// The scene to open, picked by the first command line argument: `sponza`
+/// (the default) or `cornell-box`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum SceneChoice {
+    Sponza,
+    CornellBox,
+}
+
+impl SceneChoice {
+    fn from_args() -> Self {
+        match std::env::args().nth(1).as_deref() {
+            None | Some("sponza") => Self::Sponza,
+            Some("cornell-box") => Self::CornellBox,
+            Some(other) => {
+                eprintln!("warning: unknown scene {}, opening Sponza", other);
+                Self::Sponza
+            }
+        }
+    }
+
+    fn camera(self) -> camera::Camera {
+        match self {
+            Self::Sponza => camera::Camera::new(
+                (-10.0, 7.0, 1.2),
+                cgmath::Deg(-10f32),
+                cgmath::Deg(0.0),
+                WIDTH,
+                HEIGHT,
+                45.0,
+                0.1,
+                100.0,
+            ),
+            Self::CornellBox => cornell_box::reference_camera(WIDTH, HEIGHT),
+        }
+    }
+}
+
 struct State {
     display: glium::Display,
     camera: camera::Camera,
     camera_controller: camera::CameraController,
     mouse_pressed: bool,
+    pan_pressed: bool,
 }
 
 impl State {
-    async fn new(event_loop: &glium::glutin::event_loop::EventLoop<()>) -> Self {
-        use glium::{glutin, Surface};
+    async fn new(
+        event_loop: &glium::glutin::event_loop::EventLoop<()>,
+        camera: camera::Camera,
+    ) -> Self {
+        use glium::glutin;
 
         let wb = glutin::window::WindowBuilder::new()
             .with_title("Diffue GI")
@@ -37,18 +112,8 @@ impl State {
             .with_vsync(false)
             .with_double_buffer(Some(true));
 
-        let display = glium::Display::new(wb, cb, &event_loop).unwrap();
-
-        let camera = camera::Camera::new(
-            (-10.0, 7.0, 1.2),
-            cgmath::Deg(-10f32),
-            cgmath::Deg(0.0),
-            WIDTH,
-            HEIGHT,
-            45.0,
-            0.1,
-            100.0,
-        );
+        let display = glium::Display::new(wb, cb, event_loop).unwrap();
+
         let camera_controller = camera::CameraController::new(2.0, 0.6);
 
         Self {
@@ -56,10 +121,11 @@ impl State {
             camera,
             camera_controller,
             mouse_pressed: false,
+            pan_pressed: false,
         }
     }
 
-    pub fn get_display_ref(self: &Self) -> &glium::Display {
+    pub fn get_display_ref(&self) -> &glium::Display {
         &self.display
     }
 
@@ -68,7 +134,7 @@ impl State {
         self.camera_controller.update_camera(&mut self.camera, dt);
     }
 
-    fn input(self: &mut Self, event: &glium::glutin::event::WindowEvent) -> bool {
+    fn input(&mut self, event: &glium::glutin::event::WindowEvent) -> bool {
         match event {
             glium::glutin::event::WindowEvent::KeyboardInput {
                 input:
@@ -91,6 +157,14 @@ impl State {
                 self.mouse_pressed = *state == glium::glutin::event::ElementState::Pressed;
                 true
             }
+            glium::glutin::event::WindowEvent::MouseInput {
+                button: glium::glutin::event::MouseButton::Right,
+                state,
+                ..
+            } => {
+                self.pan_pressed = *state == glium::glutin::event::ElementState::Pressed;
+                true
+            }
             _ => false,
         }
     }
@@ -109,30 +183,475 @@ impl State {
     }
 }
 
+/// Editors for a node's translation, rotation (as Euler angles in degrees)
+/// and per-axis scale. Returns whether anything changed.
+fn transform_ui(ui: &mut egui::Ui, transform: &mut Transform) -> bool {
+    let euler = cgmath::Euler::from(transform.rotation);
+    let mut angles = [euler.x, euler.y, euler.z].map(|angle| cgmath::Deg::from(angle).0);
+    let mut changed = false;
+
+    for (label, values, speed) in [
+        ("Position", transform.translation.as_mut(), 0.1),
+        ("Rotation", &mut angles, 1.0),
+        ("Scale", transform.scale.as_mut(), 0.01),
+    ] {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            for value in values.iter_mut() {
+                changed |= ui.add(egui::DragValue::new(value).speed(speed)).changed();
+            }
+        });
+    }
+
+    if changed {
+        let [x, y, z] = angles.map(|angle| cgmath::Rad::from(cgmath::Deg(angle)));
+        transform.rotation = cgmath::Euler::new(x, y, z).into();
+    }
+    changed
+}
+
+/// Controls for the projection, the clip planes and what scrolling does,
+/// and buttons framing `scene_bounds` from the top, front or side. Reverse-Z
+/// can only be turned on when `reverse_z_supported`.
+fn projection_ui(
+    ui: &mut egui::Ui,
+    camera: &mut camera::Camera,
+    controller: &mut camera::CameraController,
+    scene_bounds: Option<bounds::BoundingSphere>,
+    reverse_z_supported: bool,
+) {
+    if let Some(sphere) = scene_bounds {
+        ui.horizontal(|ui| {
+            for (preset, label) in [
+                (camera::ViewPreset::Top, "Top"),
+                (camera::ViewPreset::Front, "Front"),
+                (camera::ViewPreset::Side, "Side"),
+            ] {
+                if ui.button(label).clicked() {
+                    camera.set_view_preset(preset, sphere.center, sphere.radius);
+                    controller.set_focus(sphere.center);
+                }
+            }
+        });
+    }
+
+    let mut scroll_mode = controller.get_scroll_mode();
+    ui.horizontal(|ui| {
+        ui.label("Scroll");
+        ui.radio_value(&mut scroll_mode, camera::ScrollMode::Move, "Move");
+        ui.radio_value(&mut scroll_mode, camera::ScrollMode::Zoom, "Zoom");
+    });
+    controller.set_scroll_mode(scroll_mode);
+
+    let aspect_ratio = *camera.get_aspect_ratio();
+    let projection = camera.get_projection_mut();
+    let mut mode = projection.get_mode();
+    ui.horizontal(|ui| {
+        ui.radio_value(
+            &mut mode,
+            camera::ProjectionMode::Perspective,
+            "Perspective",
+        );
+        ui.radio_value(
+            &mut mode,
+            camera::ProjectionMode::Orthographic,
+            "Orthographic",
+        );
+    });
+    projection.set_mode(mode);
+
+    match mode {
+        camera::ProjectionMode::Perspective => {
+            let mut fovy = cgmath::Deg::from(projection.get_fovy());
+            if ui
+                .add(egui::Slider::new(&mut fovy.0, 1.0..=120.0).text("Vertical FOV"))
+                .changed()
+            {
+                projection.set_fovy(fovy);
+            }
+        }
+        camera::ProjectionMode::Orthographic => {
+            let mut height = projection.get_orthographic_height();
+            ui.horizontal(|ui| {
+                ui.label("View height");
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut height)
+                            .speed(0.1)
+                            .clamp_range(0.01..=10_000.0),
+                    )
+                    .changed()
+                {
+                    projection.set_orthographic_height(height);
+                }
+            });
+        }
+    }
+
+    let mut reverse_z = projection.is_reverse_z();
+    if ui
+        .add_enabled(
+            reverse_z_supported,
+            egui::Checkbox::new(&mut reverse_z, "Reverse-Z, infinite far plane"),
+        )
+        .changed()
+    {
+        projection.set_reverse_z(reverse_z);
+    }
+    // The reverse-Z perspective has no far plane to edit.
+    let far_plane_used = !(reverse_z && mode == camera::ProjectionMode::Perspective);
+
+    let (mut znear, mut zfar) = (projection.get_znear(), projection.get_zfar());
+    ui.horizontal(|ui| {
+        ui.label("Clip planes");
+        if ui
+            .add(
+                egui::DragValue::new(&mut znear)
+                    .speed(0.01)
+                    .clamp_range(0.001..=zfar * 0.999),
+            )
+            .changed()
+        {
+            projection.set_znear(znear);
+        }
+        if ui
+            .add_enabled(
+                far_plane_used,
+                egui::DragValue::new(&mut zfar)
+                    .speed(1.0)
+                    .clamp_range(znear * 1.001..=100_000.0),
+            )
+            .changed()
+        {
+            projection.set_zfar(zfar);
+        }
+        ui.label(format!("aspect {:.3}", aspect_ratio));
+    });
+}
+
+/// Editors for the camera path: recording the current view, adjusting or
+/// removing keyframes, playback and saving.
+fn camera_path_ui(
+    ui: &mut egui::Ui,
+    path: &mut camera_path::CameraPath,
+    camera: &mut camera::Camera,
+    playback: &mut Option<camera_path::Playback>,
+) {
+    use camera_path::{Interpolation, Keyframe, Playback};
+
+    let mut interpolation = path.get_interpolation();
+    ui.horizontal(|ui| {
+        ui.radio_value(&mut interpolation, Interpolation::CatmullRom, "Catmull-Rom");
+        ui.radio_value(&mut interpolation, Interpolation::Bezier, "Bezier");
+    });
+    path.set_interpolation(interpolation);
+
+    let mut keyframes = path.get_keyframes().to_vec();
+    let mut changed = false;
+    let mut removed = None;
+    for (index, keyframe) in keyframes.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut keyframe.time)
+                        .speed(0.05)
+                        .suffix(" s"),
+                )
+                .changed();
+            for value in AsMut::<[f32; 3]>::as_mut(&mut keyframe.position) {
+                changed |= ui.add(egui::DragValue::new(value).speed(0.1)).changed();
+            }
+            for angle in [&mut keyframe.yaw, &mut keyframe.pitch] {
+                let mut degrees = cgmath::Deg::from(*angle);
+                if ui
+                    .add(egui::DragValue::new(&mut degrees.0).suffix("°"))
+                    .changed()
+                {
+                    *angle = degrees.into();
+                    changed = true;
+                }
+            }
+            if ui.button("Go to").clicked() {
+                keyframe.apply(camera);
+            }
+            if ui.button("Remove").clicked() {
+                removed = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed {
+        keyframes.remove(index);
+        changed = true;
+    }
+    if changed {
+        path.set_keyframes(keyframes);
+    }
+
+    ui.horizontal(|ui| {
+        if ui.button("Add keyframe").clicked() {
+            let time = match path.get_keyframes() {
+                [] => 0.0,
+                _ => path.get_duration() + 1.0,
+            };
+            path.add_keyframe(Keyframe::from_camera(time, camera));
+        }
+        match playback {
+            Some(current) => {
+                ui.label(format!("{:.2} s", current.get_time()));
+                if ui.button("Stop").clicked() {
+                    *playback = None;
+                }
+            }
+            None => {
+                if ui.button("Play").clicked() {
+                    *playback = Some(Playback::new());
+                }
+            }
+        }
+        let file = std::path::Path::new(CAMERA_PATH_FILE);
+        if ui.button("Save").clicked() {
+            if let Err(error) = path.save(file) {
+                eprintln!("error: can't save {}: {}", CAMERA_PATH_FILE, error);
+            }
+        }
+        if ui.button("Load").clicked() {
+            match camera_path::CameraPath::load(file) {
+                Ok(loaded) => *path = loaded,
+                Err(error) => eprintln!("error: can't load {}: {}", CAMERA_PATH_FILE, error),
+            }
+        }
+    });
+}
+
+/// Adds a row of primitives, one per kind, as children of `parent`.
+fn add_test_shapes(
+    scene: &mut Scene,
+    parent: NodeId,
+    display: &glium::Display,
+    texture_cache: &mut texture_cache::TextureCache,
+) {
+    let shapes = [
+        (
+            "Sphere",
+            Primitive::Sphere {
+                radius: 0.1,
+                segments: 32,
+                rings: 16,
+            },
+        ),
+        (
+            "Cube",
+            Primitive::Cube {
+                size: cgmath::Vector3::new(0.2, 0.2, 0.2),
+            },
+        ),
+        (
+            "Plane",
+            Primitive::Plane {
+                width: 0.2,
+                depth: 0.2,
+            },
+        ),
+        (
+            "Cylinder",
+            Primitive::Cylinder {
+                radius: 0.1,
+                height: 0.2,
+                segments: 32,
+            },
+        ),
+        (
+            "Cone",
+            Primitive::Cone {
+                radius: 0.1,
+                height: 0.2,
+                segments: 32,
+            },
+        ),
+        (
+            "Torus",
+            Primitive::Torus {
+                major_radius: 0.08,
+                minor_radius: 0.03,
+                segments: 32,
+                sides: 16,
+            },
+        ),
+    ];
+
+    let shapes_node = scene.add_node("Test shapes", Some(parent), Transform::default());
+    for (index, (name, primitive)) in shapes.into_iter().enumerate() {
+        let hue = index as f32 / shapes.len() as f32;
+        let material = MaterialData {
+            diffuse_color: [hue, 0.5, 1.0 - hue],
+            ..Default::default()
+        };
+        let meshes = primitive.build_mesh(material).into_iter().collect();
+        let data = primitives::model_data(name, meshes);
+        let model = Model::upload(display, data, texture_cache).unwrap();
+
+        let node = scene.add_node(
+            name,
+            Some(shapes_node),
+            Transform {
+                translation: cgmath::Vector3::new(index as f32 * 0.4 - 1.0, 1.0, 0.0),
+                ..Default::default()
+            },
+        );
+        scene.set_model(node, model);
+    }
+}
+
+/// A collapsible editor for a node, with its children nested inside.
+fn node_ui(ui: &mut egui::Ui, scene: &mut Scene, id: NodeId) {
+    let node = scene.get_node(id);
+    let children = node.get_children().to_vec();
+    egui::CollapsingHeader::new(node.get_name())
+        .id_source(id)
+        .show(ui, |ui| {
+            let node = scene.get_node_mut(id);
+            let world_position = node.get_world_matrix().w.truncate();
+            ui.label(format!("World position {:?}", world_position));
+            if let Some(model) = node.get_model() {
+                ui.label(format!("{} meshes", model.get_mesh_objects().len()));
+            }
+
+            let mut transform = *node.get_transform();
+            if transform_ui(ui, &mut transform) {
+                node.set_transform(transform);
+            }
+            for child in children {
+                node_ui(ui, scene, child);
+            }
+        });
+}
+
+/// Reacts to watched files being written. Models built from them load again
+/// in the background and replace their old meshes once done; a map is decoded
+/// right away and swapped into every material using it. Nodes whose load was
+/// already running are added to `stale_loads`, since that load read its files
+/// before these writes.
+fn reload_changed_files(
+    paths: &[std::path::PathBuf],
+    scene: &mut Scene,
+    pending_models: &mut Vec<(PendingModel, NodeId)>,
+    stale_loads: &mut std::collections::HashSet<NodeId>,
+    display: &glium::Display,
+    texture_cache: &mut texture_cache::TextureCache,
+) {
+    // An OBJ and its MTL saved together only need one reload.
+    let reloads: Vec<(String, NodeId)> = scene
+        .get_model_nodes()
+        .filter(|(_, model)| {
+            model
+                .get_sources()
+                .iter()
+                .any(|source| paths.contains(source))
+        })
+        .map(|(id, model)| (model.get_path().to_string_lossy().into_owned(), id))
+        .collect();
+    for (model_path, node) in reloads {
+        // A glTF's images are cached under keys derived from its path, which
+        // no watched file matches, so they are dropped here to be decoded
+        // again along with the model.
+        let model_source = texture_cache::canonical_path(std::path::Path::new(&model_path));
+        for key in texture_cache.get_cached_paths() {
+            if gltf_loader::image_source(&key).as_ref() == Some(&model_source) {
+                texture_cache.evict(&key);
+            }
+        }
+
+        if pending_models.iter().any(|(_, pending)| *pending == node) {
+            stale_loads.insert(node);
+        } else {
+            pending_models.push((PendingModel::spawn(&model_path, &[], texture_cache), node));
+        }
+    }
+
+    for path in paths {
+        if !texture_cache.evict(path) {
+            continue;
+        }
+        let image = texture_cache::ImageData::decode(path)
+            .map_err(|error| texture_cache::report_texture_failure(path, &error))
+            .ok();
+        let images = std::collections::HashMap::from([(path.clone(), image)]);
+        let nodes: Vec<NodeId> = scene.get_model_nodes().map(|(id, _)| id).collect();
+        let mut reloaded = 0;
+        for node in nodes {
+            if let Some(model) = scene.get_node_mut(node).get_model_mut() {
+                reloaded += model.reload_map(display, path, &images, texture_cache);
+            }
+        }
+        println!("reloaded {} ({} meshes use it)", path.display(), reloaded);
+    }
+}
+
+/// Places a model's light in world space for the shadow pass, which needs a
+/// position even for directional lights.
+fn light_world_position(model_transform: &cgmath::Matrix4<f32>, light: &Light) -> [f32; 3] {
+    match light.get_kind() {
+        LightKind::Directional => {
+            let [x, y, z] = *light.get_direction();
+            let direction = (model_transform * cgmath::Vector4::new(x, y, z, 0.0))
+                .truncate()
+                .normalize();
+            (-direction * DIRECTIONAL_LIGHT_DISTANCE).into()
+        }
+        _ => {
+            let [x, y, z] = *light.get_position();
+            (model_transform * cgmath::Vector4::new(x, y, z, 1.0))
+                .truncate()
+                .into()
+        }
+    }
+}
+
 fn main() {
     let app = async {
         let event_loop = glium::glutin::event_loop::EventLoop::new();
 
-        let mut state: State = State::new(&event_loop).await;
+        let scene_choice = SceneChoice::from_args();
 
-        let renderer = renderer::Renderer::new(state.get_display_ref());
-
-        let mut egui_glium = egui_glium::EguiGlium::new(state.get_display_ref());
+        let mut state: State = State::new(&event_loop, scene_choice.camera()).await;
 
-        let mut sponza = Model::new("./Sponza/sponza.obj", state.get_display_ref());
+        let mut renderer = renderer::Renderer::new(state.get_display_ref());
 
-        //sponza.set_scale(0.1);
+        let mut egui_glium = egui_glium::EguiGlium::new(state.get_display_ref());
 
-        let mut sphere_model = Model::new("./sphere.obj", state.get_display_ref());
+        let mut texture_cache = texture_cache::TextureCache::new();
+
+        let mut scene = Scene::new();
+        // Models decode in the background and join their node as they finish.
+        let mut pending_models: Vec<(PendingModel, NodeId)> = vec![];
+        let mut stale_loads = std::collections::HashSet::new();
+        match scene_choice {
+            SceneChoice::Sponza => {
+                let sponza = scene.add_node("Sponza", None, Transform::default());
+                add_test_shapes(
+                    &mut scene,
+                    sponza,
+                    state.get_display_ref(),
+                    &mut texture_cache,
+                );
+                pending_models.push((
+                    PendingModel::spawn("./Sponza/sponza.obj", &[], &texture_cache),
+                    sponza,
+                ));
+            }
+            SceneChoice::CornellBox => {
+                cornell_box::add_to_scene(&mut scene, state.get_display_ref(), &mut texture_cache)
+                    .unwrap();
+            }
+        }
 
-        sphere_model.set_position(cgmath::Vector3 {
-            x: 0.,
-            y: 1.0,
-            z: 0.,
-        });
-        sphere_model.set_scale(0.1);
+        // Without a watcher everything still works, just without reloading.
+        let mut asset_watcher = hot_reload::AssetWatcher::new()
+            .map_err(|error| eprintln!("warning: hot reloading is off: {}", error))
+            .ok();
 
-        let models = vec![sponza, sphere_model];
+        let mut camera_path = camera_path::CameraPath::new();
+        let mut playback: Option<camera_path::Playback> = None;
 
         let mut last_render_time = std::time::Instant::now();
 
@@ -157,14 +676,83 @@ fn main() {
 
                 light_t += secs * 0.5;
 
-                let light_loc = {
+                let changed = asset_watcher
+                    .as_mut()
+                    .map_or_else(Vec::new, |watcher| watcher.take_changed());
+                reload_changed_files(
+                    &changed,
+                    &mut scene,
+                    &mut pending_models,
+                    &mut stale_loads,
+                    state.get_display_ref(),
+                    &mut texture_cache,
+                );
+
+                let mut restarts = vec![];
+                pending_models.retain(|(pending, node)| match pending.try_take() {
+                    // The file changed while loading, so the data is already
+                    // out of date; load it again instead.
+                    Some(_) if stale_loads.remove(node) => {
+                        restarts.push((pending.get_path().to_owned(), *node));
+                        false
+                    }
+                    Some(data) => {
+                        let display = state.get_display_ref();
+                        let result =
+                            data.and_then(|data| match scene.get_node_mut(*node).get_model_mut() {
+                                Some(model) => model.update(display, data, &mut texture_cache).map(
+                                    |uploaded| {
+                                        println!(
+                                            "reloaded {} ({} meshes changed)",
+                                            pending.get_path(),
+                                            uploaded
+                                        )
+                                    },
+                                ),
+                                None => Model::upload(display, data, &mut texture_cache)
+                                    .map(|model| scene.set_model(*node, model)),
+                            });
+                        match result {
+                            Ok(()) => {
+                                if let Some(watcher) = asset_watcher.as_mut() {
+                                    let model = scene.get_node(*node).get_model().unwrap();
+                                    for source in model.get_sources() {
+                                        watcher.watch(source);
+                                    }
+                                    for map in texture_cache.get_cached_paths() {
+                                        watcher.watch(&map);
+                                    }
+                                }
+                            }
+                            // A failed reload keeps the model as it was.
+                            Err(error) => eprintln!("error: {}", error),
+                        }
+                        false
+                    }
+                    None => true,
+                });
+                for (path, node) in restarts {
+                    pending_models.push((PendingModel::spawn(&path, &[], &texture_cache), node));
+                }
+
+                scene.update_world_matrices();
+
+                // The first light shipped with a model replaces the animated one.
+                let scene_light = scene.get_models().find_map(|(model, matrix)| {
+                    model
+                        .get_lights()
+                        .first()
+                        .map(|light| (light_world_position(&matrix, light), *light.get_color()))
+                });
+
+                let (light_loc, light_color) = scene_light.unwrap_or_else(|| {
                     let x = 1.0 * light_t.cos();
                     let z = 2.0 * light_t.sin();
-                    [x as f32, 15.0, z as f32]
-                };
+                    ([x as f32, 15.0, z as f32], DEFAULT_LIGHT_COLOR)
+                });
                 //println!("{:?}", light_loc);
 
-                let repaint_after = egui_glium.run(state.get_display_ref(), |egui_ctx| {
+                let repaint_after = egui_glium.run(&state.display, |egui_ctx| {
                     egui::Window::new("Shadow map")
                         .resizable(true)
                         .collapsible(true)
@@ -176,7 +764,88 @@ fn main() {
                     egui::SidePanel::left("my_side_panel").show(egui_ctx, |ui| {
                         ui.heading(format!("Last render time {:?}", dt.as_micros()));
                         ui.label(format!("FPS {:?}", (1000000.0 / dt.as_micros() as f32)));
-                        ui.label(format!("Camera {:?}", &state.camera.position));
+                        ui.label(format!("Camera {:?}", state.camera.get_view_position()));
+                        let mut mode = state.camera_controller.get_mode();
+                        ui.horizontal(|ui| {
+                            ui.radio_value(&mut mode, camera::CameraMode::FreeFly, "Free fly");
+                            ui.radio_value(&mut mode, camera::CameraMode::Orbit, "Orbit");
+                        });
+                        state.camera_controller.set_mode(mode);
+                        if mode == camera::CameraMode::Orbit {
+                            ui.label(format!(
+                                "Focus {:?} (right drag to pan)",
+                                state.camera_controller.get_focus()
+                            ));
+                        }
+                        ui.label(format!(
+                            "Textures {} ({:.1} MiB)",
+                            texture_cache.get_texture_count(),
+                            texture_cache.get_memory_usage() as f32 / (1024.0 * 1024.0)
+                        ));
+                        for light in scene.get_models().flat_map(|(model, _)| model.get_lights()) {
+                            ui.label(format!(
+                                "{:?} light, color {:?}, intensity {}, range {:?}",
+                                light.get_kind(),
+                                light.get_color(),
+                                light.get_intensity(),
+                                light.get_range()
+                            ));
+                        }
+                        for (model, matrix) in scene.get_models() {
+                            if let (Some(bounds), Some(sphere)) = (
+                                model.get_transformed_bounds(&matrix),
+                                model.get_transformed_bounding_sphere(&matrix),
+                            ) {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!(
+                                        "{} bounds {:?}..{:?}, size {:?}, radius {:.2}",
+                                        model.get_path().display(),
+                                        bounds.min,
+                                        bounds.max,
+                                        bounds.get_size(),
+                                        sphere.radius
+                                    ));
+                                    if ui.button("Orbit").clicked() {
+                                        state.camera_controller.set_focus(bounds.get_center());
+                                        state.camera_controller.set_mode(camera::CameraMode::Orbit);
+                                    }
+                                });
+                            }
+                        }
+                        for root in scene.get_roots().to_vec() {
+                            node_ui(ui, &mut scene, root);
+                        }
+                        for (pending, _) in &pending_models {
+                            ui.add(
+                                egui::ProgressBar::new(pending.get_progress())
+                                    .text(format!("Loading {}", pending.get_path())),
+                            );
+                        }
+                        egui::CollapsingHeader::new("Projection").show(ui, |ui| {
+                            let scene_bounds = scene
+                                .get_models()
+                                .filter_map(|(model, matrix)| {
+                                    model.get_transformed_bounding_sphere(&matrix)
+                                })
+                                .reduce(|sphere, model_sphere| sphere.union(&model_sphere));
+                            projection_ui(
+                                ui,
+                                &mut state.camera,
+                                &mut state.camera_controller,
+                                scene_bounds,
+                                renderer.supports_reverse_z(),
+                            );
+                        });
+                        egui::CollapsingHeader::new("Camera path").show(ui, |ui| {
+                            camera_path_ui(ui, &mut camera_path, &mut state.camera, &mut playback);
+                        });
+                        let mut anisotropy = renderer.get_anisotropy();
+                        if ui
+                            .add(egui::Slider::new(&mut anisotropy, 1..=16).text("Anisotropy"))
+                            .changed()
+                        {
+                            renderer.set_anisotropy(anisotropy);
+                        }
                         if ui.button("Quit").clicked() {
                             println!("clicked");
                             quit = true;
@@ -198,19 +867,33 @@ fn main() {
                 };
 
                 {
-                    state.update(dt);
-
-                    renderer.render_shadows(state.get_display_ref(), &models, &light_loc.into());
+                    // Playback steps a fixed time per frame and takes over from
+                    // the controller until the path ends.
+                    match playback.as_mut() {
+                        Some(current) => {
+                            if !current.advance(&camera_path, &mut state.camera) {
+                                playback = None;
+                            }
+                        }
+                        None => state.update(dt),
+                    }
 
-                    use glium::Surface;
+                    renderer.render_shadows(state.get_display_ref(), &scene, &light_loc);
 
                     // draw things behind egui here
                     let mut target = state.get_display_ref().draw();
 
                     let color = egui::Rgba::from_rgb(0.53, 0.81, 0.92);
-                    target.clear_color_and_depth((color[0], color[1], color[2], color[3]), 1.0);
 
-                    renderer.render_scene(&mut target, &state.camera, &models, &light_loc.into());
+                    renderer.render_scene(
+                        state.get_display_ref(),
+                        &mut target,
+                        (color[0], color[1], color[2], color[3]),
+                        &state.camera,
+                        &scene,
+                        &light_loc,
+                        &light_color,
+                    );
 
                     egui_glium.paint(state.get_display_ref(), &mut target);
 
@@ -232,11 +915,11 @@ fn main() {
                 glium::glutin::event::Event::DeviceEvent {
                     event: glium::glutin::event::DeviceEvent::MouseMotion { delta },
                     ..
-                } => {
-                    if state.mouse_pressed {
-                        state.camera_controller.process_mouse(delta.0, delta.1)
-                    }
-                }
+                } if state.mouse_pressed => state.camera_controller.process_mouse(delta.0, delta.1),
+                glium::glutin::event::Event::DeviceEvent {
+                    event: glium::glutin::event::DeviceEvent::MouseMotion { delta },
+                    ..
+                } if state.pan_pressed => state.camera_controller.process_pan(delta.0, delta.1),
 
                 glium::glutin::event::Event::WindowEvent { ref event, .. }
                     if !state.input(event) =>
@@ -269,7 +952,7 @@ fn main() {
                         *control_flow = glium::glutin::event_loop::ControlFlow::Exit;
                     }
 
-                    egui_glium.on_event(&event);
+                    egui_glium.on_event(event);
 
                     state
                         .get_display_ref()
@@ -0,0 +1,583 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime};
+
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+
+use crate::model_data::{
+    AlphaMode, GroupGeometry, MaterialData, MtlLibrary, MtlMaterial, ObjGeometry, SamplerSettings,
+    Vertex,
+};
+
+const MAGIC: &[u8; 8] = b"DGIMESH\0";
+/// Bump whenever the layout below or the mesh processing feeding it changes,
+/// so stale caches are rebuilt rather than misread.
+const VERSION: u32 = 4;
+
+/// Numbers the partial files of this process, so two threads writing the
+/// same cache don't share one.
+static PARTIAL_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Sampler settings are stored as their index in these lists.
+const WRAP_FUNCTIONS: [SamplerWrapFunction; 5] = [
+    SamplerWrapFunction::Repeat,
+    SamplerWrapFunction::Mirror,
+    SamplerWrapFunction::Clamp,
+    SamplerWrapFunction::BorderClamp,
+    SamplerWrapFunction::MirrorClamp,
+];
+const MINIFY_FILTERS: [MinifySamplerFilter; 6] = [
+    MinifySamplerFilter::Nearest,
+    MinifySamplerFilter::Linear,
+    MinifySamplerFilter::NearestMipmapNearest,
+    MinifySamplerFilter::LinearMipmapNearest,
+    MinifySamplerFilter::NearestMipmapLinear,
+    MinifySamplerFilter::LinearMipmapLinear,
+];
+const MAGNIFY_FILTERS: [MagnifySamplerFilter; 2] =
+    [MagnifySamplerFilter::Nearest, MagnifySamplerFilter::Linear];
+
+/// What the cache of an OBJ holds.
+pub struct MeshCache {
+    pub geometry: ObjGeometry,
+    /// The OBJ's MTL libraries as last parsed. Each is checked against its
+    /// file by the loader, independently of the OBJ.
+    pub libraries: Vec<MtlLibrary>,
+}
+
+/// Where the cache of `source` lives: next to it, as `<name>.meshcache`.
+pub fn cache_path(source: &Path) -> PathBuf {
+    let mut name = source.file_name().unwrap_or_default().to_owned();
+    name.push(".meshcache");
+    source.with_file_name(name)
+}
+
+/// FNV-1a, which is stable across runs and builds, unlike the std hashers.
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn hash_file(path: &Path) -> io::Result<u64> {
+    Ok(hash_bytes(&std::fs::read(path)?))
+}
+
+/// Writes the welded geometry of the OBJ at `source` to its cache, along with
+/// a hash of the OBJ and its parsed MTL `libraries`. Material maps are kept
+/// as the libraries name them: the files they resolve to depend on the search
+/// paths and on which textures exist, so they are found again on every load.
+pub fn write(source: &Path, geometry: &ObjGeometry, libraries: &[MtlLibrary]) -> io::Result<()> {
+    // Written aside and renamed into place, so a concurrent reader never sees
+    // half a cache.
+    let path = cache_path(source);
+    let partial = path.with_extension(format!(
+        "meshcache.{}.{}",
+        std::process::id(),
+        PARTIAL_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let mut writer = Writer(BufWriter::new(File::create(&partial)?));
+
+    writer.bytes(MAGIC)?;
+    writer.u32(VERSION)?;
+    writer.u64(hash_file(source)?)?;
+
+    writer.len(geometry.material_libraries.len())?;
+    for library in &geometry.material_libraries {
+        writer.string(library)?;
+    }
+    writer.len(geometry.groups.len())?;
+    for group in &geometry.groups {
+        writer.group(group)?;
+    }
+    writer.len(libraries.len())?;
+    for library in libraries {
+        writer.library(library)?;
+    }
+    writer.0.into_inner().map_err(|error| error.into_error())?;
+
+    std::fs::rename(&partial, &path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&partial);
+    })
+}
+
+/// Reads the cached geometry and libraries of the OBJ at `source`. Returns
+/// `Ok(None)` when there is no cache or it is stale: older than, or hashed
+/// from different contents than, the OBJ, or written by another version. A
+/// cache that can't be parsed is an error.
+pub fn read(source: &Path) -> io::Result<Option<MeshCache>> {
+    let path = cache_path(source);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    let cache_modified = file.metadata()?.modified()?;
+    let mut reader = Reader(BufReader::new(file));
+
+    let mut magic = [0; 8];
+    reader.0.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a mesh cache"));
+    }
+    if reader.u32()? != VERSION {
+        return Ok(None);
+    }
+
+    let hash = reader.u64()?;
+    let modified = match std::fs::metadata(source) {
+        Ok(metadata) => metadata.modified()?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error),
+    };
+    if modified > cache_modified || hash_file(source)? != hash {
+        return Ok(None);
+    }
+
+    let library_count = reader.len()?;
+    let mut material_libraries = Vec::with_capacity(library_count);
+    for _ in 0..library_count {
+        material_libraries.push(reader.string()?);
+    }
+    let group_count = reader.len()?;
+    let mut groups = Vec::with_capacity(group_count);
+    for _ in 0..group_count {
+        groups.push(reader.group()?);
+    }
+    let library_count = reader.len()?;
+    let mut libraries = Vec::with_capacity(library_count);
+    for _ in 0..library_count {
+        libraries.push(reader.library()?);
+    }
+    Ok(Some(MeshCache {
+        geometry: ObjGeometry {
+            material_libraries,
+            groups,
+        },
+        libraries,
+    }))
+}
+
+/// The position of `value` in `list`, which holds every variant.
+fn index_of<T: PartialEq>(list: &[T], value: &T) -> u8 {
+    list.iter().position(|item| item == value).unwrap() as u8
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+/// Little-endian encoder for the cache layout.
+struct Writer<W: Write>(W);
+
+impl<W: Write> Writer<W> {
+    fn bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.0.write_all(bytes)
+    }
+
+    fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.bytes(&[value])
+    }
+
+    fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> io::Result<()> {
+        self.bytes(&value.to_le_bytes())
+    }
+
+    fn f32s(&mut self, values: &[f32]) -> io::Result<()> {
+        values.iter().try_for_each(|&value| self.f32(value))
+    }
+
+    fn len(&mut self, len: usize) -> io::Result<()> {
+        let len = u32::try_from(len).map_err(|_| invalid_data("too many elements"))?;
+        self.u32(len)
+    }
+
+    fn string(&mut self, string: &str) -> io::Result<()> {
+        self.len(string.len())?;
+        self.bytes(string.as_bytes())
+    }
+
+    fn optional_string(&mut self, string: Option<&str>) -> io::Result<()> {
+        match string {
+            Some(string) => {
+                self.u8(1)?;
+                self.string(string)
+            }
+            None => self.u8(0),
+        }
+    }
+
+    fn group(&mut self, group: &GroupGeometry) -> io::Result<()> {
+        self.string(&group.object)?;
+        self.string(&group.group)?;
+        self.optional_string(group.material.as_deref())?;
+
+        self.len(group.vertices.len())?;
+        for vertex in &group.vertices {
+            self.f32s(&vertex.position)?;
+            self.f32s(&vertex.tex_coord)?;
+            self.f32s(&vertex.normal)?;
+            self.f32s(&vertex.tangent)?;
+        }
+        self.len(group.indices.len())?;
+        for &index in &group.indices {
+            self.u32(index)?;
+        }
+        Ok(())
+    }
+
+    fn library(&mut self, library: &MtlLibrary) -> io::Result<()> {
+        self.string(&library.name)?;
+        self.u64(library.hash)?;
+        let modified = library
+            .modified
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_err(|_| invalid_data("modified before 1970"))?;
+        self.u64(modified.as_secs())?;
+        self.u32(modified.subsec_nanos())?;
+        self.len(library.materials.len())?;
+        for material in &library.materials {
+            self.string(&material.name)?;
+            self.material(&material.material)?;
+            let settings = &material.sampler_settings;
+            self.u8(index_of(&WRAP_FUNCTIONS, &settings.wrap))?;
+            self.u8(index_of(&MINIFY_FILTERS, &settings.minify))?;
+            self.u8(index_of(&MAGNIFY_FILTERS, &settings.magnify))?;
+        }
+        Ok(())
+    }
+
+    fn material(&mut self, material: &MaterialData) -> io::Result<()> {
+        self.f32s(&material.ambient_color)?;
+        self.f32s(&material.diffuse_color)?;
+        self.f32s(&material.specular_color)?;
+        self.f32s(&material.emissive_color)?;
+        self.f32s(&material.transmission_filter)?;
+        self.f32(material.shininess)?;
+        self.f32(material.optical_density)?;
+        self.f32(material.dissolve)?;
+        match material.alpha_mode {
+            AlphaMode::Opaque => self.u8(0)?,
+            AlphaMode::Mask(cutoff) => {
+                self.u8(1)?;
+                self.f32(cutoff)?;
+            }
+            AlphaMode::Blend => self.u8(2)?,
+        }
+        self.u32(material.illumination_model as u32)?;
+        match material.metallic_roughness {
+            Some(factors) => {
+                self.u8(1)?;
+                self.f32s(&factors)?;
+            }
+            None => self.u8(0)?,
+        }
+        for map in material_maps(material) {
+            let name = map
+                .as_ref()
+                .map(|map| {
+                    map.to_str()
+                        .ok_or_else(|| invalid_data("map name is not UTF-8"))
+                })
+                .transpose()?;
+            self.optional_string(name)?;
+        }
+        Ok(())
+    }
+}
+
+/// A material's maps in the order the cache stores them.
+fn material_maps(material: &MaterialData) -> [&Option<PathBuf>; 8] {
+    [
+        &material.ambient_map,
+        &material.diffuse_map,
+        &material.specular_map,
+        &material.emissive_map,
+        &material.shininess_map,
+        &material.dissolve_map,
+        &material.normal_map,
+        &material.metallic_roughness_map,
+    ]
+}
+
+/// Decoder matching `Writer`.
+struct Reader<R: Read>(R);
+
+impl<R: Read> Reader<R> {
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.0.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn f32s<const N: usize>(&mut self) -> io::Result<[f32; N]> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            *value = f32::from_le_bytes(self.array()?);
+        }
+        Ok(values)
+    }
+
+    fn len(&mut self) -> io::Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let mut bytes = vec![0; self.len()?];
+        self.0.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|_| invalid_data("string is not UTF-8"))
+    }
+
+    fn optional_string(&mut self) -> io::Result<Option<String>> {
+        match self.u8()? {
+            0 => Ok(None),
+            _ => self.string().map(Some),
+        }
+    }
+
+    /// Reads an index written by `index_of` back into `list`.
+    fn variant<T: Copy>(&mut self, list: &[T]) -> io::Result<T> {
+        list.get(self.u8()? as usize)
+            .copied()
+            .ok_or_else(|| invalid_data("unknown sampler setting"))
+    }
+
+    fn group(&mut self) -> io::Result<GroupGeometry> {
+        // Fields are read in the order `Writer::group` wrote them.
+        let object = self.string()?;
+        let group = self.string()?;
+        let material = self.optional_string()?;
+
+        let vertex_count = self.len()?;
+        let mut vertices = Vec::with_capacity(vertex_count);
+        for _ in 0..vertex_count {
+            vertices.push(Vertex {
+                position: self.f32s()?,
+                tex_coord: self.f32s()?,
+                normal: self.f32s()?,
+                tangent: self.f32s()?,
+            });
+        }
+        let index_count = self.len()?;
+        let mut indices = Vec::with_capacity(index_count);
+        for _ in 0..index_count {
+            let index = self.u32()?;
+            if index as usize >= vertex_count {
+                return Err(invalid_data("index out of range"));
+            }
+            indices.push(index);
+        }
+
+        Ok(GroupGeometry {
+            object,
+            group,
+            material,
+            vertices,
+            indices,
+        })
+    }
+
+    fn library(&mut self) -> io::Result<MtlLibrary> {
+        // Fields are read in the order `Writer::library` wrote them.
+        let name = self.string()?;
+        let hash = self.u64()?;
+        let secs = self.u64()?;
+        let nanos = self.u32()?;
+        let modified = SystemTime::UNIX_EPOCH + Duration::new(secs, nanos);
+        let material_count = self.len()?;
+        let mut materials = Vec::with_capacity(material_count);
+        for _ in 0..material_count {
+            materials.push(MtlMaterial {
+                name: self.string()?,
+                material: self.material()?,
+                sampler_settings: SamplerSettings {
+                    wrap: self.variant(&WRAP_FUNCTIONS)?,
+                    minify: self.variant(&MINIFY_FILTERS)?,
+                    magnify: self.variant(&MAGNIFY_FILTERS)?,
+                },
+            });
+        }
+        Ok(MtlLibrary {
+            name,
+            hash,
+            modified,
+            materials,
+        })
+    }
+
+    fn material(&mut self) -> io::Result<MaterialData> {
+        let mut material = MaterialData {
+            ambient_color: self.f32s()?,
+            diffuse_color: self.f32s()?,
+            specular_color: self.f32s()?,
+            emissive_color: self.f32s()?,
+            transmission_filter: self.f32s()?,
+            shininess: self.f32s::<1>()?[0],
+            optical_density: self.f32s::<1>()?[0],
+            dissolve: self.f32s::<1>()?[0],
+            alpha_mode: match self.u8()? {
+                0 => AlphaMode::Opaque,
+                1 => AlphaMode::Mask(self.f32s::<1>()?[0]),
+                _ => AlphaMode::Blend,
+            },
+            illumination_model: self.u32()? as i32,
+            metallic_roughness: match self.u8()? {
+                0 => None,
+                _ => Some(self.f32s()?),
+            },
+            ..Default::default()
+        };
+        for map in [
+            &mut material.ambient_map,
+            &mut material.diffuse_map,
+            &mut material.specular_map,
+            &mut material.emissive_map,
+            &mut material.shininess_map,
+            &mut material.dissolve_map,
+            &mut material.normal_map,
+            &mut material.metallic_roughness_map,
+        ] {
+            *map = self.optional_string()?.map(PathBuf::from);
+        }
+        Ok(material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model_data::ModelData;
+    use crate::model_loader::LoadProgress;
+    use crate::test_util::{scratch_crate, scratch_dir_with};
+    use crate::texture_cache::canonical_path;
+    use std::collections::HashSet;
+
+    fn load(path: &Path, search_paths: &[PathBuf]) -> ModelData {
+        ModelData::load(
+            path.to_str().unwrap(),
+            search_paths,
+            &HashSet::new(),
+            &LoadProgress::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn loading_writes_a_cache_that_reads_back_the_same_geometry() {
+        let source = scratch_crate("mesh_cache_round_trip");
+        let loaded = load(&source, &[]);
+
+        let cached = read(&source).unwrap().expect("cache was written");
+        let geometry = &cached.geometry;
+        assert_eq!(geometry.material_libraries, vec!["crate.mtl".to_owned()]);
+        assert_eq!(geometry.groups.len(), loaded.meshes.len());
+        for (cached, loaded) in geometry.groups.iter().zip(&loaded.meshes) {
+            assert_eq!(cached.vertices, loaded.vertices);
+            assert_eq!(cached.indices, loaded.indices);
+        }
+        assert_eq!(cached.libraries.len(), 1);
+        let material = &cached.libraries[0].materials[0];
+        assert_eq!(material.name, "Material");
+        assert_eq!(material.material, loaded.meshes[0].material);
+
+        let reloaded = load(&source, &[]);
+        for (reloaded, loaded) in reloaded.meshes.iter().zip(&loaded.meshes) {
+            assert_eq!(reloaded.material, loaded.material);
+            assert_eq!(reloaded.sampler_settings, loaded.sampler_settings);
+            assert_eq!(reloaded.bounds, loaded.bounds);
+        }
+    }
+
+    #[test]
+    fn changed_sources_invalidate_the_cache() {
+        let source = scratch_crate("mesh_cache_invalidation");
+        load(&source, &[]);
+        assert!(read(&source).unwrap().is_some());
+
+        // Same length and no newer timestamp than the cache, so only the
+        // hash can tell.
+        let modified = std::fs::metadata(&source).unwrap().modified().unwrap();
+        let mut contents = std::fs::read(&source).unwrap();
+        let last = contents.len() - 1;
+        contents[last] = if contents[last] == b'\n' { b' ' } else { b'\n' };
+        std::fs::write(&source, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+        assert!(read(&source).unwrap().is_none());
+    }
+
+    #[test]
+    fn cached_loads_resolve_materials_again() {
+        let dir = scratch_dir_with(
+            "mesh_cache_materials",
+            &[
+                (
+                    "model/wall.obj",
+                    b"mtllib wall.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl wall\nf 1 2 3\n",
+                ),
+                ("model/wall.mtl", b"newmtl wall\nmap_Kd brick.png\n"),
+            ],
+        );
+        let source = dir.join("model/wall.obj");
+        let diffuse_map = |data: &ModelData| data.meshes[0].material.diffuse_map.clone();
+        assert_eq!(diffuse_map(&load(&source, &[])), Some("brick.png".into()));
+
+        // A texture added later, and a new search path, are both picked up
+        // while the geometry still comes from the cache.
+        std::fs::create_dir_all(dir.join("shared")).unwrap();
+        std::fs::write(dir.join("shared/brick.png"), b"").unwrap();
+        let data = load(&source, &[dir.join("shared")]);
+        assert_eq!(
+            diffuse_map(&data),
+            Some(canonical_path(&dir.join("shared/brick.png")))
+        );
+        // The cache keeps the map as the library names it.
+        let cached = read(&source).unwrap().expect("cache was written");
+        assert_eq!(
+            cached.libraries[0].materials[0].material.diffuse_map,
+            Some("brick.png".into())
+        );
+
+        std::fs::write(dir.join("model/wall.mtl"), b"newmtl other\n").unwrap();
+        let data = load(&source, &[]);
+        assert!(read(&source).unwrap().is_some());
+        assert_eq!(diffuse_map(&data), None);
+    }
+
+    #[test]
+    fn garbage_is_an_error() {
+        let source = scratch_crate("mesh_cache_garbage");
+        std::fs::write(cache_path(&source), b"not a cache at all").unwrap();
+
+        assert!(read(&source).is_err());
+        // Loading recovers by parsing the OBJ and replacing the cache.
+        load(&source, &[]);
+        assert!(read(&source).unwrap().is_some());
+    }
+}
@@ -1,30 +1,138 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use glium::Display;
-use image::{self, GenericImageView};
-use obj::Obj;
-
-#[derive(Copy, Clone)]
-pub struct Vertex {
-    position: [f32; 3],
-    tex_coord: [f32; 2],
-    normal: [f32; 3],
+
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::light::Light;
+use crate::load_error::{BufferUploadError, ModelLoadError};
+use crate::model_data::{AlphaMode, MaterialData, MeshData, ModelData, SamplerSettings, Vertex};
+use crate::model_loader::LoadProgress;
+use crate::texture_cache::{ImageData, TextureCache};
+use crate::transform::Transform;
+
+/// Which maps a material has, so the shader knows which samplers to read.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct MapFlags {
+    pub ambient: bool,
+    pub diffuse: bool,
+    pub specular: bool,
+    pub emissive: bool,
+    pub shininess: bool,
+    pub dissolve: bool,
+    pub normal: bool,
+    pub metallic_roughness: bool,
 }
-implement_vertex!(Vertex, position, tex_coord, normal);
 
-pub struct MeshObject {
-    vertices: glium::VertexBuffer<Vertex>,
-    diffuse_texture: glium::texture::SrgbTexture2d,
+impl MapFlags {
+    fn from_material(material: &MaterialData) -> Self {
+        Self {
+            ambient: material.ambient_map.is_some(),
+            diffuse: material.diffuse_map.is_some(),
+            specular: material.specular_map.is_some(),
+            emissive: material.emissive_map.is_some(),
+            shininess: material.shininess_map.is_some(),
+            dissolve: material.dissolve_map.is_some(),
+            normal: material.normal_map.is_some(),
+            metallic_roughness: material.metallic_roughness_map.is_some(),
+        }
+    }
+}
+
+/// Surface parameters and texture maps of one material. Maps the material
+/// doesn't name, and data maps that failed to load, are left as 1x1
+/// placeholders and flagged off in `get_map_flags`.
+pub struct Material {
     ambient_color: [f32; 3],
     diffuse_color: [f32; 3],
     specular_color: [f32; 3],
+    emissive_color: [f32; 3],
+    transmission_filter: [f32; 3],
+    shininess: f32,
+    optical_density: f32,
+    dissolve: f32,
+    alpha_mode: AlphaMode,
+    illumination_model: i32,
+    ambient_texture: Rc<glium::texture::SrgbTexture2d>,
+    diffuse_texture: Rc<glium::texture::SrgbTexture2d>,
+    specular_texture: Rc<glium::texture::SrgbTexture2d>,
+    emissive_texture: Rc<glium::texture::SrgbTexture2d>,
+    shininess_texture: Rc<glium::texture::Texture2d>,
+    dissolve_texture: Rc<glium::texture::Texture2d>,
+    normal_texture: Rc<glium::texture::Texture2d>,
+    metallic_roughness: Option<[f32; 2]>,
+    metallic_roughness_texture: Rc<glium::texture::Texture2d>,
+    map_flags: MapFlags,
 }
 
-impl MeshObject {
-    pub fn get_vertices(&self) -> &glium::VertexBuffer<Vertex> {
-        &self.vertices
+impl Material {
+    /// Uploads the material's maps, taking their pixels from `images` or,
+    /// for maps decoded by an earlier model, from the texture cache.
+    pub fn upload(
+        display: &Display,
+        material: MaterialData,
+        images: &Images,
+        texture_cache: &mut TextureCache,
+    ) -> Self {
+        let mut map_flags = MapFlags::from_material(&material);
+        let mut srgb = |map| upload_srgb_texture(display, images, texture_cache, map);
+        let ambient_texture = srgb(material.ambient_map);
+        let diffuse_texture = srgb(material.diffuse_map);
+        let specular_texture = srgb(material.specular_map);
+        let emissive_texture = srgb(material.emissive_map);
+
+        // A data map that failed to load is dropped rather than marked, so
+        // the material falls back to its constant values.
+        let mut linear = |map: Option<PathBuf>, has_map: &mut bool| {
+            let texture =
+                map.and_then(|key| upload_linear_texture(display, images, texture_cache, key));
+            *has_map = texture.is_some();
+            texture.unwrap_or_else(|| texture_cache.get_linear_placeholder(display))
+        };
+        let shininess_texture = linear(material.shininess_map, &mut map_flags.shininess);
+        let dissolve_texture = linear(material.dissolve_map, &mut map_flags.dissolve);
+        let normal_texture = linear(material.normal_map, &mut map_flags.normal);
+        let metallic_roughness_texture = linear(
+            material.metallic_roughness_map,
+            &mut map_flags.metallic_roughness,
+        );
+
+        Self {
+            ambient_color: material.ambient_color,
+            diffuse_color: material.diffuse_color,
+            specular_color: material.specular_color,
+            emissive_color: material.emissive_color,
+            transmission_filter: material.transmission_filter,
+            shininess: material.shininess,
+            optical_density: material.optical_density,
+            dissolve: material.dissolve,
+            alpha_mode: material.alpha_mode,
+            illumination_model: material.illumination_model,
+            ambient_texture,
+            diffuse_texture,
+            specular_texture,
+            emissive_texture,
+            shininess_texture,
+            dissolve_texture,
+            normal_texture,
+            metallic_roughness: material.metallic_roughness,
+            metallic_roughness_texture,
+            map_flags,
+        }
     }
 
-    pub fn get_diffuse_texture(&self) -> &glium::texture::SrgbTexture2d {
-        &self.diffuse_texture
+    pub fn get_map_flags(&self) -> &MapFlags {
+        &self.map_flags
+    }
+
+    pub fn get_alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    /// Whether the material has to be blended over what is behind it.
+    pub fn is_blended(&self) -> bool {
+        self.alpha_mode == AlphaMode::Blend
     }
 
     pub fn get_ambient_color(&self) -> &[f32; 3] {
@@ -38,173 +146,373 @@ impl MeshObject {
     pub fn get_specular_color(&self) -> &[f32; 3] {
         &self.specular_color
     }
-}
 
-pub struct Model {
-    objects: Vec<MeshObject>,
-    position: cgmath::Vector3<f32>,
-    scale: f32,
-}
+    pub fn get_emissive_color(&self) -> &[f32; 3] {
+        &self.emissive_color
+    }
 
-impl Model {
-    pub fn new(path: &str, display: &Display) -> Self {
-        let mut obj = Obj::load(path).unwrap();
-        obj.load_mtls().unwrap();
+    pub fn get_transmission_filter(&self) -> &[f32; 3] {
+        &self.transmission_filter
+    }
 
-        let mut materials: Vec<&std::sync::Arc<obj::Material>> = vec![];
+    pub fn get_shininess(&self) -> f32 {
+        self.shininess
+    }
 
-        for mtl in obj.data.material_libs.iter() {
-            for material in mtl.materials.iter() {
-                materials.push(material);
-            }
-        }
+    pub fn get_optical_density(&self) -> f32 {
+        self.optical_density
+    }
 
-        let mut objects: Vec<MeshObject> = vec![];
+    pub fn get_dissolve(&self) -> f32 {
+        self.dissolve
+    }
 
-        for object in obj.data.objects {
-            for group in object.groups {
-                let mut positions: Vec<[f32; 3]> = vec![];
-                let mut tex_coords: Vec<[f32; 2]> = vec![];
-                let mut normals: Vec<[f32; 3]> = vec![];
+    pub fn get_illumination_model(&self) -> i32 {
+        self.illumination_model
+    }
 
-                for obj::SimplePolygon(poly) in group.polys {
-                    for vertex in poly {
-                        let obj::IndexTuple(v, vt, vn) = vertex;
-                        let v_index = v;
-                        let vt_index = vt.unwrap();
-                        let vn_index = vn.unwrap();
+    pub fn get_ambient_texture(&self) -> &glium::texture::SrgbTexture2d {
+        &self.ambient_texture
+    }
 
-                        //(obj.data.normal[vnIndex]);
-                        // positions.push(obj.data.position[vIndex]);
-                        // indices.push(v as u16);
+    pub fn get_diffuse_texture(&self) -> &glium::texture::SrgbTexture2d {
+        &self.diffuse_texture
+    }
 
-                        let vertex_position: [f32; 3] = obj.data.position[v_index];
+    pub fn get_specular_texture(&self) -> &glium::texture::SrgbTexture2d {
+        &self.specular_texture
+    }
 
-                        positions.push(vertex_position);
-                        tex_coords.push(obj.data.texture[vt_index]);
-                        normals.push(obj.data.normal[vn_index]); //([0f32, 0f32, 0f32]);
-                    }
-                }
+    pub fn get_emissive_texture(&self) -> &glium::texture::SrgbTexture2d {
+        &self.emissive_texture
+    }
+
+    pub fn get_shininess_texture(&self) -> &glium::texture::Texture2d {
+        &self.shininess_texture
+    }
+
+    pub fn get_dissolve_texture(&self) -> &glium::texture::Texture2d {
+        &self.dissolve_texture
+    }
+
+    pub fn get_normal_texture(&self) -> &glium::texture::Texture2d {
+        &self.normal_texture
+    }
+
+    /// The glTF metallic and roughness factors, or `None` for MTL materials.
+    pub fn get_metallic_roughness(&self) -> Option<[f32; 2]> {
+        self.metallic_roughness
+    }
 
-                // for index in (0..positions.len()).step_by(3) {
-                //     let va = positions[index as usize];
-                //     let vb = positions[index as usize + 1];
-                //     let vc = positions[index as usize + 2];
+    pub fn get_metallic_roughness_texture(&self) -> &glium::texture::Texture2d {
+        &self.metallic_roughness_texture
+    }
+}
 
-                //     let a = cgmath::Vector3::new(va[0], va[1], va[2]);
-                //     let b = cgmath::Vector3::new(vb[0], vb[1], vb[2]);
-                //     let c = cgmath::Vector3::new(vc[0], vc[1], vc[2]);
+pub struct MeshObject {
+    vertices: glium::VertexBuffer<Vertex>,
+    indices: glium::IndexBuffer<u32>,
+    material: Material,
+    sampler_settings: SamplerSettings,
+    bounds: Aabb,
+    bounding_sphere: BoundingSphere,
+    material_data: MaterialData,
+    geometry_hash: u64,
+}
 
-                //     let edgeAB = b - a;
-                //     let edgeAC = c - a;
+impl MeshObject {
+    pub fn upload(
+        display: &Display,
+        mesh: MeshData,
+        images: &Images,
+        texture_cache: &mut TextureCache,
+    ) -> Result<Self, BufferUploadError> {
+        let geometry_hash = mesh.get_geometry_hash();
+        let material_data = mesh.material.clone();
+        Ok(Self {
+            vertices: glium::VertexBuffer::new(display, &mesh.vertices)
+                .map_err(BufferUploadError::Vertices)?,
+            indices: glium::IndexBuffer::new(
+                display,
+                glium::index::PrimitiveType::TrianglesList,
+                &mesh.indices,
+            )
+            .map_err(BufferUploadError::Indices)?,
+            material: Material::upload(display, mesh.material, images, texture_cache),
+            sampler_settings: mesh.sampler_settings,
+            bounds: mesh.bounds,
+            bounding_sphere: mesh.bounding_sphere,
+            material_data,
+            geometry_hash,
+        })
+    }
 
-                //     let areaWeightedNormal = edgeAB.cross(edgeAC);
+    /// Whether uploading `mesh` would give this same object again.
+    fn was_built_from(&self, mesh: &MeshData) -> bool {
+        self.geometry_hash == mesh.get_geometry_hash()
+            && self.material_data == mesh.material
+            && self.sampler_settings == mesh.sampler_settings
+    }
 
-                //     let normalA = normals[index as usize];
-                //     let normalB = normals[index as usize + 1];
-                //     let normalC = normals[index as usize + 2];
+    pub fn get_vertices(&self) -> &glium::VertexBuffer<Vertex> {
+        &self.vertices
+    }
 
-                //     let mut AN = cgmath::Vector3::new(normalA[0], normalA[1], normalA[2]);
-                //     let mut BN = cgmath::Vector3::new(normalB[0], normalB[1], normalB[2]);
-                //     let mut CN = cgmath::Vector3::new(normalC[0], normalC[1], normalC[2]);
+    pub fn get_indices(&self) -> &glium::IndexBuffer<u32> {
+        &self.indices
+    }
 
-                //     AN += areaWeightedNormal;
-                //     BN += areaWeightedNormal;
-                //     CN += areaWeightedNormal;
+    pub fn get_material(&self) -> &Material {
+        &self.material
+    }
 
-                //     normals[index as usize] = [AN.x, AN.y, AN.z];
-                //     normals[index as usize + 1] = [BN.x, BN.y, BN.z];
-                //     normals[index as usize + 2] = [CN.x, CN.y, CN.z];
-                // }
+    pub fn get_sampler_settings(&self) -> &SamplerSettings {
+        &self.sampler_settings
+    }
 
-                let vertices: Vec<Vertex> = positions
-                    .iter()
-                    .enumerate()
-                    .map(|(i, &position)| Vertex {
-                        position: position,
-                        tex_coord: tex_coords[i],
-                        normal: normals[i],
-                    })
-                    .collect();
-
-                let mut diffuse_texture =
-                    glium::texture::SrgbTexture2d::empty(display, 1, 1).unwrap();
-                let base_path = "./Sponza/".to_owned();
-
-                let mut ambient_color: [f32; 3] = [0f32; 3];
-                let mut diffuse_color: [f32; 3] = [0f32; 3];
-                let mut specular_color: [f32; 3] = [0f32; 3];
-
-                match group.material.unwrap() {
-                    obj::ObjMaterial::Ref(_) => todo!(),
-                    obj::ObjMaterial::Mtl(material) => {
-                        match material.map_kd.as_ref() {
-                            Some(diffuse_path) => {
-                                let diffuse_path = base_path + diffuse_path;
-                                let diffuse_image = image::io::Reader::open(diffuse_path)
-                                    .unwrap()
-                                    .decode()
-                                    .unwrap();
-                                let raw_image = glium::texture::RawImage2d::from_raw_rgba(
-                                    (&diffuse_image.to_rgba8()).to_vec(),
-                                    diffuse_image.dimensions(),
-                                );
-                                diffuse_texture =
-                                    glium::texture::SrgbTexture2d::new(display, raw_image).unwrap();
-                            }
-                            None => {}
-                        };
-                        match material.ka.as_ref() {
-                            Some(ka) => ambient_color = *ka,
-                            None => {}
-                        }
-                        match material.kd.as_ref() {
-                            Some(kd) => diffuse_color = *kd,
-                            None => {}
-                        }
-                        match material.ks.as_ref() {
-                            Some(ks) => specular_color = *ks,
-                            None => {}
-                        }
+    /// Model-space bounding box.
+    pub fn get_bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    /// Model-space bounding sphere.
+    pub fn get_bounding_sphere(&self) -> &BoundingSphere {
+        &self.bounding_sphere
+    }
+}
+
+pub struct Model {
+    path: PathBuf,
+    sources: Vec<PathBuf>,
+    objects: Vec<MeshObject>,
+    lights: Vec<Light>,
+    transform: Transform,
+}
+
+impl Model {
+    /// Loads and uploads the model at `path` on the calling thread, which
+    /// blocks until every mesh and image is done. The viewer itself loads
+    /// through `PendingModel` so the window stays responsive.
+    #[allow(dead_code)]
+    pub fn new(
+        path: &str,
+        display: &Display,
+        texture_cache: &mut TextureCache,
+    ) -> Result<Self, ModelLoadError> {
+        let data = ModelData::load(
+            path,
+            &[],
+            &texture_cache.get_cached_paths(),
+            &LoadProgress::default(),
+        )?;
+        Self::upload(display, data, texture_cache)
+    }
+
+    /// Creates the GPU resources for data loaded by `ModelData::load`. Maps
+    /// that failed to decode are replaced by a magenta placeholder with a
+    /// warning rather than failing the whole model.
+    pub fn upload(
+        display: &Display,
+        data: ModelData,
+        texture_cache: &mut TextureCache,
+    ) -> Result<Self, ModelLoadError> {
+        let ModelData {
+            path,
+            sources,
+            meshes,
+            images,
+            lights,
+            scale,
+        } = data;
+
+        let objects = meshes
+            .into_iter()
+            .map(|mesh| {
+                MeshObject::upload(display, mesh, &images, texture_cache).map_err(|source| {
+                    ModelLoadError::Upload {
+                        path: path.clone(),
+                        source,
                     }
-                };
+                })
+            })
+            .collect::<Result<_, _>>()?;
 
-                let object: MeshObject = MeshObject {
-                    vertices: glium::VertexBuffer::new(display, &vertices).unwrap(),
-                    diffuse_texture,
-                    ambient_color,
-                    diffuse_color,
-                    specular_color,
-                };
+        Ok(Self {
+            path,
+            sources,
+            objects,
+            lights,
+            transform: Transform::from_scale(scale),
+        })
+    }
 
-                objects.push(object);
+    /// Swaps in freshly loaded data for the same file, uploading only the
+    /// meshes whose geometry, material, sampling or maps changed. On failure the
+    /// model is left as it was. Returns the number of meshes uploaded.
+    pub fn update(
+        &mut self,
+        display: &Display,
+        data: ModelData,
+        texture_cache: &mut TextureCache,
+    ) -> Result<usize, ModelLoadError> {
+        let ModelData {
+            path,
+            sources,
+            meshes,
+            images,
+            lights,
+            scale: _,
+        } = data;
+
+        let uploads = meshes
+            .into_iter()
+            .enumerate()
+            .map(|(index, mesh)| {
+                // Maps in `images` were decoded again, so meshes using them
+                // need new textures even when nothing else changed.
+                let unchanged = self
+                    .objects
+                    .get(index)
+                    .is_some_and(|object| object.was_built_from(&mesh))
+                    && !mesh.material.get_maps().any(|map| images.contains_key(map));
+                if unchanged {
+                    return Ok(None);
+                }
+                MeshObject::upload(display, mesh, &images, texture_cache)
+                    .map(Some)
+                    .map_err(|source| ModelLoadError::Upload {
+                        path: path.clone(),
+                        source,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let uploaded = uploads.iter().filter(|upload| upload.is_some()).count();
+        let mut previous = std::mem::take(&mut self.objects).into_iter();
+        self.objects = uploads
+            .into_iter()
+            .map(|upload| {
+                let old = previous.next();
+                upload.or(old).expect("unchanged meshes have an old object")
+            })
+            .collect();
+        self.sources = sources;
+        self.lights = lights;
+        Ok(uploaded)
+    }
+
+    /// Uploads the material of every mesh using the map cached under `key`
+    /// again, taking the new pixels from `images`. Returns the number of
+    /// meshes affected.
+    pub fn reload_map(
+        &mut self,
+        display: &Display,
+        key: &Path,
+        images: &Images,
+        texture_cache: &mut TextureCache,
+    ) -> usize {
+        let mut reloaded = 0;
+        for object in &mut self.objects {
+            if object.material_data.get_maps().any(|map| map == key) {
+                object.material =
+                    Material::upload(display, object.material_data.clone(), images, texture_cache);
+                reloaded += 1;
             }
         }
-        println!("finished loading models");
+        reloaded
+    }
 
-        let position = cgmath::Vector3::new(0., 0., 0.);
+    /// The file the model was loaded from, or its name for generated ones.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
 
-        Self {
-            objects,
-            position,
-            scale: 0.01,
-        }
+    /// Canonical paths of the files whose changes should reload the model.
+    pub fn get_sources(&self) -> &[PathBuf] {
+        &self.sources
     }
 
     pub fn get_mesh_objects(&self) -> &Vec<MeshObject> {
         &self.objects
     }
 
-    pub fn set_position(&mut self, position: cgmath::Vector3<f32>) {
-        self.position = position;
+    /// Punctual lights that came with the model, in model space.
+    pub fn get_lights(&self) -> &[Light] {
+        &self.lights
     }
 
-    pub fn set_scale(&mut self, scale: f32) {
-        self.scale = scale;
+    /// Placement within the scene node holding the model; loaders use it to
+    /// bring their units to meters.
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
     }
 
-    pub fn get_transform(&self) -> [[f32; 4]; 4] {
-        (cgmath::Matrix4::from_translation(self.position) * cgmath::Matrix4::from_scale(self.scale))
-            .into()
+    /// Box around every mesh once placed by `transform`, such as the world
+    /// matrix from `Scene::get_models`, or `None` for a model without any.
+    pub fn get_transformed_bounds(&self, transform: &cgmath::Matrix4<f32>) -> Option<Aabb> {
+        self.objects
+            .iter()
+            .map(|object| object.get_bounds().transformed(transform))
+            .reduce(|bounds, object_bounds| bounds.union(&object_bounds))
+    }
+
+    /// Sphere around every mesh once placed by `transform`, or `None` for a
+    /// model without any.
+    pub fn get_transformed_bounding_sphere(
+        &self,
+        transform: &cgmath::Matrix4<f32>,
+    ) -> Option<BoundingSphere> {
+        self.objects
+            .iter()
+            .map(|object| object.get_bounding_sphere().transformed(transform))
+            .reduce(|sphere, object_sphere| sphere.union(&object_sphere))
+    }
+}
+
+/// Decoded maps waiting for upload, by texture cache key; `None` for maps
+/// that failed to decode.
+pub type Images = HashMap<PathBuf, Option<ImageData>>;
+
+/// Uploads a color map, or returns the shared placeholder when the material
+/// has none.
+fn upload_srgb_texture(
+    display: &Display,
+    images: &Images,
+    texture_cache: &mut TextureCache,
+    map: Option<PathBuf>,
+) -> Rc<glium::texture::SrgbTexture2d> {
+    match map {
+        Some(key) => match images.get(&key) {
+            Some(Some(image)) => {
+                texture_cache.get_srgb_with(display, key, || Ok(image.to_raw_image()))
+            }
+            Some(None) => {
+                texture_cache.record_failure(key);
+                texture_cache.get_srgb_missing(display)
+            }
+            // Already cached, by this model or an earlier one.
+            None => texture_cache.get_srgb(display, &key),
+        },
+        None => texture_cache.get_srgb_placeholder(display),
+    }
+}
+
+/// Uploads a data map (normals, dissolve, shininess), or returns `None` when
+/// it failed to load.
+fn upload_linear_texture(
+    display: &Display,
+    images: &Images,
+    texture_cache: &mut TextureCache,
+    key: PathBuf,
+) -> Option<Rc<glium::texture::Texture2d>> {
+    match images.get(&key) {
+        Some(Some(image)) => {
+            texture_cache.get_linear_with(display, key, || Ok(image.to_raw_image()))
+        }
+        Some(None) => {
+            texture_cache.record_failure(key);
+            None
+        }
+        None => texture_cache.get_linear(display, &key),
     }
 }
@@ -0,0 +1,1473 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use cgmath::Point3;
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, SamplerWrapFunction};
+use obj::Obj;
+use rayon::prelude::*;
+
+use crate::asset_resolver::AssetResolver;
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::gltf_loader;
+use crate::light::Light;
+use crate::load_error::ModelLoadError;
+use crate::mesh_cache::{self, MeshCache};
+use crate::model_loader::LoadProgress;
+use crate::texture_cache::{canonical_path, report_texture_failure, ImageData};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub tex_coord: [f32; 2],
+    pub normal: [f32; 3],
+    pub tangent: [f32; 4],
+}
+implement_vertex!(Vertex, position, tex_coord, normal, tangent);
+
+/// OBJ materials that aren't see-through cut out texels less opaque than
+/// this.
+const MTL_ALPHA_CUTOFF: f32 = 0.1;
+
+/// How a material's opacity, from `dissolve` and the diffuse and dissolve
+/// maps, is used.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AlphaMode {
+    /// Opacity is ignored.
+    Opaque,
+    /// Texels less opaque than the cutoff are discarded and the rest drawn
+    /// opaque.
+    Mask(f32),
+    /// Blended over whatever is behind.
+    Blend,
+}
+
+/// Surface parameters of a material, with its maps named by texture cache
+/// key rather than uploaded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialData {
+    pub ambient_color: [f32; 3],
+    pub diffuse_color: [f32; 3],
+    pub specular_color: [f32; 3],
+    pub emissive_color: [f32; 3],
+    pub transmission_filter: [f32; 3],
+    pub shininess: f32,
+    pub optical_density: f32,
+    pub dissolve: f32,
+    pub alpha_mode: AlphaMode,
+    pub illumination_model: i32,
+    /// glTF metallic and roughness factors; `None` for MTL materials.
+    pub metallic_roughness: Option<[f32; 2]>,
+    pub ambient_map: Option<PathBuf>,
+    pub diffuse_map: Option<PathBuf>,
+    pub specular_map: Option<PathBuf>,
+    pub emissive_map: Option<PathBuf>,
+    pub shininess_map: Option<PathBuf>,
+    pub dissolve_map: Option<PathBuf>,
+    pub normal_map: Option<PathBuf>,
+    pub metallic_roughness_map: Option<PathBuf>,
+}
+
+/// The plain light grey material given to OBJ groups without one.
+impl Default for MaterialData {
+    fn default() -> Self {
+        Self {
+            ambient_color: [0.0; 3],
+            diffuse_color: [0.8; 3],
+            specular_color: [0.5; 3],
+            emissive_color: [0.0; 3],
+            transmission_filter: [1.0; 3],
+            shininess: 32.0,
+            optical_density: 1.0,
+            dissolve: 1.0,
+            alpha_mode: AlphaMode::Mask(MTL_ALPHA_CUTOFF),
+            illumination_model: 2,
+            metallic_roughness: None,
+            ambient_map: None,
+            diffuse_map: None,
+            specular_map: None,
+            emissive_map: None,
+            shininess_map: None,
+            dissolve_map: None,
+            normal_map: None,
+            metallic_roughness_map: None,
+        }
+    }
+}
+
+impl MaterialData {
+    /// Reads an MTL material. Its maps keep the file names the statements
+    /// give until `with_resolved_maps` finds them.
+    pub fn from_mtl(material: &obj::Material) -> Self {
+        // `d` wins over `Tr` when a file sets both, as most exporters intend.
+        let dissolve = material
+            .d
+            .or_else(|| material.tr.map(|tr| 1.0 - tr))
+            .unwrap_or(1.0);
+        let map = |map: &Option<String>| map.as_deref().map(|map| texture_file_name(map).into());
+
+        Self {
+            ambient_color: material.ka.unwrap_or([0.0; 3]),
+            diffuse_color: material.kd.unwrap_or([0.0; 3]),
+            specular_color: material.ks.unwrap_or([0.0; 3]),
+            emissive_color: material.ke.unwrap_or([0.0; 3]),
+            transmission_filter: material.tf.unwrap_or([1.0; 3]),
+            shininess: material.ns.unwrap_or(0.0),
+            optical_density: material.ni.unwrap_or(1.0),
+            dissolve,
+            // A `map_d` alone is a cut-out mask, as on foliage and chains, which
+            // keeps its depth writes; only see-through surfaces are blended.
+            alpha_mode: if dissolve < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Mask(MTL_ALPHA_CUTOFF)
+            },
+            illumination_model: material.illum.unwrap_or(2),
+            metallic_roughness: None,
+            ambient_map: map(&material.map_ka),
+            diffuse_map: map(&material.map_kd),
+            specular_map: map(&material.map_ks),
+            emissive_map: map(&material.map_ke),
+            shininess_map: map(&material.map_ns),
+            dissolve_map: map(&material.map_d),
+            normal_map: map(&material.map_bump),
+            metallic_roughness_map: None,
+        }
+    }
+
+    /// Resolves the map file names of an MTL material to texture cache keys.
+    /// A file that can't be found keeps its name, so it fails to decode and
+    /// is reported then.
+    fn with_resolved_maps(mut self, resolver: &AssetResolver) -> Self {
+        for map in [
+            &mut self.ambient_map,
+            &mut self.diffuse_map,
+            &mut self.specular_map,
+            &mut self.emissive_map,
+            &mut self.shininess_map,
+            &mut self.dissolve_map,
+            &mut self.normal_map,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            if let Some(path) = map.to_str().and_then(|name| resolver.resolve(name)) {
+                *map = canonical_path(&path);
+            }
+        }
+        self
+    }
+
+    /// Every map the material names.
+    pub fn get_maps(&self) -> impl Iterator<Item = &PathBuf> {
+        [
+            &self.ambient_map,
+            &self.diffuse_map,
+            &self.specular_map,
+            &self.emissive_map,
+            &self.shininess_map,
+            &self.dissolve_map,
+            &self.normal_map,
+            &self.metallic_roughness_map,
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// How a mesh's material maps are wrapped and filtered when sampled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SamplerSettings {
+    pub wrap: SamplerWrapFunction,
+    pub minify: MinifySamplerFilter,
+    pub magnify: MagnifySamplerFilter,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            wrap: SamplerWrapFunction::Repeat,
+            minify: MinifySamplerFilter::LinearMipmapLinear,
+            magnify: MagnifySamplerFilter::Linear,
+        }
+    }
+}
+
+impl SamplerSettings {
+    /// Derives the settings from a material's diffuse map statement, which
+    /// may carry `-clamp on`.
+    pub fn from_mtl(material: &obj::Material) -> Self {
+        let clamp = material.map_kd.as_deref().is_some_and(|map| {
+            split_texture_options(map)
+                .0
+                .iter()
+                .any(|(option, args)| *option == "-clamp" && args.first() == Some(&"on"))
+        });
+
+        Self {
+            wrap: if clamp {
+                SamplerWrapFunction::Clamp
+            } else {
+                SamplerWrapFunction::Repeat
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// A welded triangle mesh ready for upload.
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    pub material: MaterialData,
+    pub sampler_settings: SamplerSettings,
+    /// Model-space bounds of the vertices.
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
+}
+
+impl MeshData {
+    /// A hash of the vertices and indices, to tell whether a reloaded mesh
+    /// changed shape.
+    pub fn get_geometry_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        for vertex in &self.vertices {
+            let Vertex {
+                position,
+                tex_coord,
+                normal,
+                tangent,
+            } = vertex;
+            for value in position
+                .iter()
+                .chain(tex_coord)
+                .chain(normal)
+                .chain(tangent)
+            {
+                value.to_bits().hash(&mut hasher);
+            }
+        }
+        self.indices.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Wraps a mesh, computing its bounds. Returns `None` for a mesh with no
+    /// triangles, which has nothing to draw or bound.
+    pub fn new(
+        vertices: Vec<Vertex>,
+        indices: Vec<u32>,
+        material: MaterialData,
+        sampler_settings: SamplerSettings,
+    ) -> Option<Self> {
+        if indices.is_empty() {
+            return None;
+        }
+
+        let positions = vertices.iter().map(|vertex| Point3::from(vertex.position));
+        Some(Self {
+            bounds: Aabb::from_points(positions.clone()),
+            bounding_sphere: BoundingSphere::from_points(positions),
+            vertices,
+            indices,
+            material,
+            sampler_settings,
+        })
+    }
+}
+
+/// Everything a `Model` is built from, decoded without touching the GPU so it
+/// can be produced on worker threads.
+pub struct ModelData {
+    pub path: PathBuf,
+    /// Canonical paths of the files the meshes were built from: an OBJ and
+    /// its material libraries but not its maps, which reload on their own, or
+    /// a glTF with its external buffers and images.
+    pub sources: Vec<PathBuf>,
+    pub meshes: Vec<MeshData>,
+    /// Decoded maps by texture cache key, or `None` for maps that failed to
+    /// decode and were reported. Maps that were already cached when loading
+    /// started are left out.
+    pub images: HashMap<PathBuf, Option<ImageData>>,
+    pub lights: Vec<Light>,
+    pub scale: f32,
+}
+
+impl ModelData {
+    /// Parses an OBJ or a glTF 2.0 (`.gltf`/`.glb`) file, building meshes and
+    /// decoding images in parallel on the rayon pool. OBJ MTL libraries and
+    /// textures are resolved relative to the OBJ's own directory and then
+    /// against `search_paths`; images whose keys are in `cached` are skipped.
+    /// Welded OBJ geometry and parsed MTL materials are kept in a
+    /// `mesh_cache` next to the file and read back from it while they are up
+    /// to date.
+    pub fn load(
+        path: &str,
+        search_paths: &[PathBuf],
+        cached: &HashSet<PathBuf>,
+        progress: &LoadProgress,
+    ) -> Result<Self, ModelLoadError> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        if matches!(extension.as_deref(), Some("gltf") | Some("glb")) {
+            return gltf_loader::load_gltf(path, cached, progress);
+        }
+
+        let cache = match mesh_cache::read(Path::new(path)) {
+            Ok(cache) => cache,
+            Err(error) => {
+                eprintln!("warning: ignoring the mesh cache of {}: {}", path, error);
+                None
+            }
+        };
+        let (geometry, cached_libraries, geometry_cached) = match cache {
+            Some(MeshCache {
+                geometry,
+                libraries,
+            }) => {
+                // Reading the cache counts as one step.
+                progress.add_work(1);
+                progress.complete_step();
+                (geometry, libraries, true)
+            }
+            None => (parse_obj(path, progress)?, vec![], false),
+        };
+
+        let directory = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        let resolver = AssetResolver::new(directory, search_paths);
+        let (library_paths, libraries) = load_material_libraries(
+            path,
+            &resolver,
+            &geometry.material_libraries,
+            &cached_libraries,
+        )?;
+        if !geometry_cached || libraries != cached_libraries {
+            if let Err(error) = mesh_cache::write(Path::new(path), &geometry, &libraries) {
+                eprintln!(
+                    "warning: couldn't write the mesh cache of {}: {}",
+                    path, error
+                );
+            }
+        }
+
+        // Libraries are searched in the order the OBJ lists them, and the
+        // first definition of a name wins.
+        let mut library_materials: HashMap<&str, &MtlMaterial> = HashMap::new();
+        for material in libraries.iter().flat_map(|library| &library.materials) {
+            library_materials
+                .entry(material.name.as_str())
+                .or_insert(material);
+        }
+
+        // Maps are resolved on every load, cached materials or not, since the
+        // files they name depend on the search paths and on which textures
+        // exist.
+        let meshes: Vec<MeshData> = geometry
+            .groups
+            .into_iter()
+            .filter_map(|group| {
+                let material = group
+                    .material
+                    .as_deref()
+                    .and_then(|name| library_materials.get(name));
+                let (material, sampler_settings) = match material {
+                    Some(material) => (
+                        material.material.clone().with_resolved_maps(&resolver),
+                        material.sampler_settings,
+                    ),
+                    None => {
+                        report_material_fallback(path, &group);
+                        (MaterialData::default(), SamplerSettings::default())
+                    }
+                };
+                MeshData::new(group.vertices, group.indices, material, sampler_settings)
+            })
+            .collect();
+
+        let maps = uncached_maps(&meshes, cached);
+        progress.add_work(maps.len());
+        let images = decode_maps(maps, progress);
+
+        let sources = std::iter::once(Path::new(path))
+            .chain(library_paths.iter().map(PathBuf::as_path))
+            .map(canonical_path)
+            .collect();
+        Ok(Self {
+            path: path.into(),
+            sources,
+            meshes,
+            images,
+            lights: vec![],
+            scale: OBJ_SCALE,
+        })
+    }
+}
+
+/// The welded geometry of one OBJ group, with the names its material is
+/// looked up by.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GroupGeometry {
+    pub object: String,
+    pub group: String,
+    /// The name the group's `usemtl` gives, if any.
+    pub material: Option<String>,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+/// The welded groups of an OBJ and the material libraries it lists, as the
+/// mesh cache keeps them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjGeometry {
+    /// `mtllib` names, in the order the OBJ lists them.
+    pub material_libraries: Vec<String>,
+    pub groups: Vec<GroupGeometry>,
+}
+
+/// Parses the OBJ at `path` and welds its groups on the rayon pool.
+fn parse_obj(path: &str, progress: &LoadProgress) -> Result<ObjGeometry, ModelLoadError> {
+    let obj = Obj::load(path).map_err(|source| ModelLoadError::Obj {
+        path: path.into(),
+        source,
+    })?;
+
+    let groups: Vec<(&obj::Object, &obj::Group)> = obj
+        .data
+        .objects
+        .iter()
+        .flat_map(|object| object.groups.iter().map(move |group| (object, group)))
+        .collect();
+    progress.add_work(groups.len());
+
+    let groups = groups
+        .into_par_iter()
+        .map(|(object, group)| {
+            let (vertices, indices) = build_indexed_mesh(&obj.data, &group.polys);
+            progress.complete_step();
+            GroupGeometry {
+                object: object.name.clone(),
+                group: group.name.clone(),
+                material: group.material.as_ref().map(|material| match material {
+                    obj::ObjMaterial::Ref(name) => name.clone(),
+                    obj::ObjMaterial::Mtl(material) => material.name.clone(),
+                }),
+                vertices,
+                indices,
+            }
+        })
+        .collect();
+
+    Ok(ObjGeometry {
+        material_libraries: obj
+            .data
+            .material_libs
+            .iter()
+            .map(|library| library.filename.clone())
+            .collect(),
+        groups,
+    })
+}
+
+/// An MTL material with its maps named as the library wrote them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    pub name: String,
+    pub material: MaterialData,
+    pub sampler_settings: SamplerSettings,
+}
+
+/// The parsed materials of one MTL library, which the mesh cache keeps
+/// along with the library's hash and modification time to tell when it
+/// needs parsing again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlLibrary {
+    /// The `mtllib` name the OBJ gives.
+    pub name: String,
+    pub hash: u64,
+    pub modified: SystemTime,
+    pub materials: Vec<MtlMaterial>,
+}
+
+/// Loads the MTL libraries an OBJ lists, found the same way as its maps.
+/// Libraries in `cached` whose file is unchanged are reused rather than
+/// parsed. Returns the libraries' paths along with their materials.
+fn load_material_libraries(
+    path: &str,
+    resolver: &AssetResolver,
+    names: &[String],
+    cached: &[MtlLibrary],
+) -> Result<(Vec<PathBuf>, Vec<MtlLibrary>), ModelLoadError> {
+    let mut paths = vec![];
+    let mut libraries = vec![];
+    for name in names {
+        let library = read_mtl(resolver, name)
+            .map_err(obj::MtlError::Io)
+            .and_then(|(library_path, source, modified)| {
+                let hash = mesh_cache::hash_bytes(source.as_bytes());
+                let unchanged = cached.iter().find(|library| {
+                    library.name == *name && library.hash == hash && library.modified == modified
+                });
+                let library = match unchanged {
+                    Some(library) => library.clone(),
+                    None => parse_mtl(name, &library_path, &source, hash, modified)?,
+                };
+                paths.push(library_path);
+                Ok(library)
+            })
+            .map_err(|source| ModelLoadError::MaterialLibrary {
+                path: path.into(),
+                library: name.clone(),
+                source,
+            })?;
+        libraries.push(library);
+    }
+    Ok((paths, libraries))
+}
+
+/// Warns that a group is drawn with the default material, either because it
+/// has no `usemtl` or because no loaded library defines the one it names.
+fn report_material_fallback(path: &str, group: &GroupGeometry) {
+    let reason = match &group.material {
+        Some(name) => format!("no loaded material library defines {}", name),
+        None => "it has no material".to_owned(),
+    };
+    eprintln!(
+        "warning: group {} of object {} in {} uses the default material: {}",
+        group.group, group.object, path, reason
+    );
+}
+
+/// OBJ files carry no units; ours are in centimeters.
+const OBJ_SCALE: f32 = 0.01;
+
+/// The maps `meshes` name that aren't in `cached` yet.
+fn uncached_maps(meshes: &[MeshData], cached: &HashSet<PathBuf>) -> HashSet<PathBuf> {
+    meshes
+        .iter()
+        .flat_map(|mesh| mesh.material.get_maps())
+        .filter(|map| !cached.contains(*map))
+        .cloned()
+        .collect()
+}
+
+/// Decodes `maps` on the rayon pool, reporting the ones that fail.
+fn decode_maps(
+    maps: HashSet<PathBuf>,
+    progress: &LoadProgress,
+) -> HashMap<PathBuf, Option<ImageData>> {
+    maps.into_par_iter()
+        .map(|map| {
+            let image = ImageData::decode(&map)
+                .map_err(|error| report_texture_failure(&map, &error))
+                .ok();
+            progress.complete_step();
+            (map, image)
+        })
+        .collect()
+}
+
+/// Faces meeting at a sharper angle than this keep separate normals when the
+/// OBJ does not supply its own.
+const CREASE_ANGLE: cgmath::Deg<f32> = cgmath::Deg(60.0);
+
+/// Welds identical (position, tex_coord, normal) index tuples into a shared
+/// vertex table and returns it together with a triangle list indexing into it.
+/// Polygons with more than three corners are triangulated on the way, missing
+/// normals are generated and missing texture coordinates default to zero.
+/// Texture coordinates are flipped to put v = 0 at the top of the image, the
+/// row images are uploaded from, since OBJ puts it at the bottom.
+pub fn build_indexed_mesh(
+    data: &obj::ObjData,
+    polys: &[obj::SimplePolygon],
+) -> (Vec<Vertex>, Vec<u32>) {
+    let mut triangles: Vec<[obj::IndexTuple; 3]> = vec![];
+
+    for obj::SimplePolygon(poly) in polys {
+        let corner_positions: Vec<[f32; 3]> = poly
+            .iter()
+            .map(|&obj::IndexTuple(v, _, _)| data.position[v])
+            .collect();
+
+        for [a, b, c] in triangulate_polygon(&corner_positions) {
+            triangles.push([poly[a], poly[b], poly[c]]);
+        }
+    }
+
+    let generated_normals = if triangles.iter().flatten().any(|tuple| tuple.2.is_none()) {
+        generate_normals(data, &triangles, CREASE_ANGLE.into())
+    } else {
+        vec![]
+    };
+
+    // Corners are expanded first because MikkTSpace works on unwelded
+    // triangles; corners that end up identical are welded afterwards.
+    let mut corners: Vec<Vertex> = Vec::with_capacity(triangles.len() * 3);
+    for (face, triangle) in triangles.iter().enumerate() {
+        for (corner, &obj::IndexTuple(v, vt, vn)) in triangle.iter().enumerate() {
+            corners.push(Vertex {
+                position: data.position[v],
+                tex_coord: vt.map_or([0.0, 0.0], |vt| {
+                    let [u, v] = data.texture[vt];
+                    [u, 1.0 - v]
+                }),
+                normal: match vn {
+                    Some(vn) => data.normal[vn],
+                    None => generated_normals[face][corner],
+                },
+                tangent: [1.0, 0.0, 0.0, 1.0],
+            });
+        }
+    }
+    mikktspace::generate_tangents(&mut TangentGeometry(&mut corners));
+
+    let mut vertices: Vec<Vertex> = vec![];
+    let mut indices: Vec<u32> = vec![];
+    // Generated normals and tangents are part of the key so that corners split
+    // by a crease or a UV seam stay separate vertices.
+    let mut welded: HashMap<(obj::IndexTuple, [u32; 7]), u32> = HashMap::new();
+
+    for (&index_tuple, vertex) in triangles.iter().flatten().zip(corners) {
+        let mut shading_bits = [0u32; 7];
+        for (bits, value) in shading_bits
+            .iter_mut()
+            .zip(vertex.normal.iter().chain(vertex.tangent.iter()))
+        {
+            *bits = value.to_bits();
+        }
+
+        let index = *welded
+            .entry((index_tuple, shading_bits))
+            .or_insert_with(|| {
+                vertices.push(vertex);
+                (vertices.len() - 1) as u32
+            });
+        indices.push(index);
+    }
+
+    (vertices, indices)
+}
+
+/// Triangle-list view over expanded mesh corners for tangent generation.
+struct TangentGeometry<'a>(&'a mut [Vertex]);
+
+impl mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.0.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.0[face * 3 + vert].position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.0[face * 3 + vert].normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.0[face * 3 + vert].tex_coord
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.0[face * 3 + vert].tangent = tangent;
+    }
+}
+
+/// Fills in MikkTSpace tangents for an already welded mesh. Vertices shared
+/// between triangles get the same tangent from each, since MikkTSpace itself
+/// groups corners with equal position, normal and texture coordinate.
+pub fn generate_indexed_tangents(vertices: &mut [Vertex], indices: &[u32]) {
+    mikktspace::generate_tangents(&mut IndexedTangentGeometry { vertices, indices });
+}
+
+/// Triangle-list view over a welded mesh for tangent generation.
+struct IndexedTangentGeometry<'a> {
+    vertices: &'a mut [Vertex],
+    indices: &'a [u32],
+}
+
+impl IndexedTangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl mikktspace::Geometry for IndexedTangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertex(face, vert).tex_coord
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.vertices[self.indices[face * 3 + vert] as usize].tangent = tangent;
+    }
+}
+
+/// Computes smooth per-corner normals by summing the area-weighted normals of
+/// every face sharing the corner's position, skipping faces that meet the
+/// corner's own face at more than `crease_angle`.
+fn generate_normals(
+    data: &obj::ObjData,
+    triangles: &[[obj::IndexTuple; 3]],
+    crease_angle: cgmath::Rad<f32>,
+) -> Vec<[[f32; 3]; 3]> {
+    use cgmath::{InnerSpace, Vector3};
+
+    let face_normals: Vec<Vector3<f32>> = triangles
+        .iter()
+        .map(|triangle| {
+            let [a, b, c] =
+                triangle.map(|obj::IndexTuple(v, _, _)| Vector3::from(data.position[v]));
+            let edge_ab = b - a;
+            let edge_ac = c - a;
+            edge_ab.cross(edge_ac)
+        })
+        .collect();
+
+    let mut faces_at_position: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (face, triangle) in triangles.iter().enumerate() {
+        for &obj::IndexTuple(v, _, _) in triangle {
+            faces_at_position.entry(v).or_default().push(face);
+        }
+    }
+
+    let min_cos = crease_angle.0.cos();
+
+    triangles
+        .iter()
+        .enumerate()
+        .map(|(face, triangle)| {
+            let face_normal = face_normals[face];
+            if face_normal.magnitude2() == 0.0 {
+                // Degenerate triangles cover no pixels, any unit normal will do.
+                return [[0.0, 1.0, 0.0]; 3];
+            }
+            let face_direction = face_normal.normalize();
+
+            triangle.map(|obj::IndexTuple(v, _, _)| {
+                let area_weighted_normal: Vector3<f32> = faces_at_position[&v]
+                    .iter()
+                    .map(|&other| face_normals[other])
+                    .filter(|other| {
+                        other.magnitude2() > 0.0 && other.normalize().dot(face_direction) >= min_cos
+                    })
+                    .sum();
+                area_weighted_normal.normalize().into()
+            })
+        })
+        .collect()
+}
+
+/// Statements the `obj` MTL parser reads; it rejects the whole library on
+/// any other.
+const OBJ_MTL_KEYWORDS: &[&str] = &[
+    "newmtl", "Ka", "Kd", "Ks", "Ke", "Ns", "Ni", "Km", "d", "Tr", "Tf", "illum", "map_Ka",
+    "map_Kd", "map_Ks", "map_Ns", "map_d", "map_refl", "refl", "map_bump", "map_Bump", "bump",
+];
+
+/// Finds and reads the MTL library `mtllib`, returning its path, contents
+/// and modification time.
+fn read_mtl(resolver: &AssetResolver, mtllib: &str) -> io::Result<(PathBuf, String, SystemTime)> {
+    let path = resolver.resolve(mtllib).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("material library {} not found", mtllib),
+        )
+    })?;
+    let source = std::fs::read_to_string(&path)?;
+    let modified = std::fs::metadata(&path)?.modified()?;
+    Ok((path, source, modified))
+}
+
+/// Parses the MTL library `name`, read from `path`. The `norm` statement
+/// some exporters use for normal maps becomes `map_bump` for the `obj`
+/// parser, `map_Ke`, which it can't read, is filled in afterwards, and other
+/// statements it doesn't know, such as PBR extensions, are dropped with a
+/// warning.
+fn parse_mtl(
+    name: &str,
+    path: &Path,
+    source: &str,
+    hash: u64,
+    modified: SystemTime,
+) -> Result<MtlLibrary, obj::MtlError> {
+    let mut lines = vec![];
+    let mut emissive_maps = HashMap::new();
+    let mut skipped = BTreeSet::new();
+    let mut material: Option<String> = None;
+    for line in source.lines() {
+        let statement = line.trim_start();
+        let (keyword, rest) = statement
+            .split_once(char::is_whitespace)
+            .unwrap_or((statement, ""));
+        match keyword {
+            "norm" => lines.push(format!("map_bump {}", rest)),
+            "map_Ke" => {
+                if let Some(name) = &material {
+                    emissive_maps.insert(name.clone(), rest.trim().to_owned());
+                }
+            }
+            _ if keyword.is_empty()
+                || keyword.starts_with('#')
+                || OBJ_MTL_KEYWORDS.contains(&keyword) =>
+            {
+                if keyword == "newmtl" {
+                    material = rest.split_whitespace().next().map(str::to_owned);
+                }
+                lines.push(line.to_owned());
+            }
+            _ => {
+                skipped.insert(keyword.to_owned());
+            }
+        }
+    }
+    if !skipped.is_empty() {
+        let skipped: Vec<String> = skipped.into_iter().collect();
+        eprintln!(
+            "warning: {} uses unsupported statements, ignoring them: {}",
+            path.display(),
+            skipped.join(", ")
+        );
+    }
+
+    let mut library = obj::Mtl::new(name.to_owned());
+    library.reload(lines.join("\n").as_bytes())?;
+    let materials = library
+        .materials
+        .into_iter()
+        .map(|mut material| {
+            if let Some(map) = emissive_maps.remove(&material.name) {
+                Arc::make_mut(&mut material).map_ke = Some(map);
+            }
+            MtlMaterial {
+                name: material.name.clone(),
+                material: MaterialData::from_mtl(&material),
+                sampler_settings: SamplerSettings::from_mtl(&material),
+            }
+        })
+        .collect();
+    Ok(MtlLibrary {
+        name: name.to_owned(),
+        hash,
+        modified,
+        materials,
+    })
+}
+
+/// Splits an MTL map statement such as `-bm 0.5 -s 1 1 1 brick.png` into its
+/// options, each with its arguments, and the file name.
+fn split_texture_options(map: &str) -> (Vec<(&str, Vec<&str>)>, &str) {
+    let mut options = vec![];
+    let mut rest = map.trim();
+    while rest.starts_with('-') {
+        let (option, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let max_args = match option {
+            "-o" | "-s" | "-t" => 3,
+            "-mm" => 2,
+            _ => 1,
+        };
+        rest = after.trim_start();
+        let mut args = vec![];
+        // -o, -s and -t take one to three numbers, so stop at the first word
+        // that isn't one.
+        for _ in 0..max_args {
+            let (arg, after) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if max_args > 1 && arg.parse::<f32>().is_err() {
+                break;
+            }
+            args.push(arg);
+            rest = after.trim_start();
+        }
+        options.push((option, args));
+    }
+    (options, rest)
+}
+
+/// Strips MTL texture options from a map statement, leaving the file name.
+fn texture_file_name(map: &str) -> &str {
+    split_texture_options(map).1
+}
+
+/// Splits a polygon into triangles by ear clipping and returns corner indices
+/// into `positions`. Concave polygons are handled, and every triangle keeps the
+/// winding of the input polygon.
+fn triangulate_polygon(positions: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    let n = positions.len();
+    if n < 3 {
+        return vec![];
+    }
+    if n == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method gives a stable normal even for concave polygons; the
+    // polygon is then projected onto the plane its normal is most aligned with.
+    let mut normal = [0f32; 3];
+    for i in 0..n {
+        let a = positions[i];
+        let b = positions[(i + 1) % n];
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+    let (u, v) = if normal[0].abs() >= normal[1].abs() && normal[0].abs() >= normal[2].abs() {
+        (1, 2)
+    } else if normal[1].abs() >= normal[2].abs() {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+    let points: Vec<[f32; 2]> = positions.iter().map(|p| [p[u], p[v]]).collect();
+
+    let signed_area: f32 = (0..n)
+        .map(|i| {
+            let a = points[i];
+            let b = points[(i + 1) % n];
+            a[0] * b[1] - b[0] * a[1]
+        })
+        .sum();
+    let orientation = if signed_area < 0.0 { -1.0 } else { 1.0 };
+
+    let cross = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| {
+        ((a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])) * orientation
+    };
+
+    let mut remaining: Vec<usize> = (0..n).collect();
+    let mut triangles: Vec<[usize; 3]> = Vec::with_capacity(n - 2);
+    let mut i = 0;
+    let mut misses = 0;
+
+    while remaining.len() > 3 {
+        let len = remaining.len();
+        let prev = remaining[(i + len - 1) % len];
+        let curr = remaining[i % len];
+        let next = remaining[(i + 1) % len];
+        let (a, b, c) = (points[prev], points[curr], points[next]);
+
+        let is_ear = cross(a, b, c) > 0.0
+            && remaining.iter().all(|&other| {
+                let p = points[other];
+                other == prev
+                    || other == curr
+                    || other == next
+                    || p == a
+                    || p == b
+                    || p == c
+                    || cross(a, b, p) < 0.0
+                    || cross(b, c, p) < 0.0
+                    || cross(c, a, p) < 0.0
+            });
+
+        // A full lap without an ear means the rest is degenerate (collinear or
+        // self-intersecting), so clip anyway rather than loop forever.
+        if is_ear || misses >= len {
+            triangles.push([prev, curr, next]);
+            remaining.remove(i % len);
+            misses = 0;
+        } else {
+            i += 1;
+            misses += 1;
+        }
+        i %= remaining.len();
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{scratch_crate, scratch_dir_with};
+
+    /// Expands every index tuple into its own (position, tex_coord, normal)
+    /// corner, the way the loader used to before welding.
+    fn build_expanded_mesh(
+        data: &obj::ObjData,
+        polys: &[obj::SimplePolygon],
+    ) -> Vec<([f32; 3], [f32; 2], [f32; 3])> {
+        polys
+            .iter()
+            .flat_map(|obj::SimplePolygon(poly)| poly.iter())
+            .map(|&obj::IndexTuple(v, vt, vn)| {
+                let [s, t] = data.texture[vt.unwrap()];
+                (data.position[v], [s, 1.0 - t], data.normal[vn.unwrap()])
+            })
+            .collect()
+    }
+
+    fn assert_same_triangles(path: &str) {
+        let data = obj::Obj::load(path).unwrap().data;
+
+        for object in data.objects.iter() {
+            for group in object.groups.iter() {
+                let expanded = build_expanded_mesh(&data, &group.polys);
+                let (vertices, indices) = build_indexed_mesh(&data, &group.polys);
+
+                assert_eq!(indices.len(), expanded.len());
+                assert!(vertices.len() <= expanded.len());
+                for (&index, expected) in indices.iter().zip(expanded.iter()) {
+                    let vertex = &vertices[index as usize];
+                    assert_eq!(
+                        &(vertex.position, vertex.tex_coord, vertex.normal),
+                        expected
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn indexed_crate_matches_expanded() {
+        assert_same_triangles("./crate.obj");
+    }
+
+    #[test]
+    fn indexed_sphere_matches_expanded() {
+        assert_same_triangles("./sphere.obj");
+    }
+
+    #[test]
+    fn shared_tuples_are_welded() {
+        let src = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1\nf 3/1/1 2/1/1 4/1/1\n";
+        let data = obj::ObjData::load_buf(&src[..]).unwrap();
+        let polys = &data.objects[0].groups[0].polys;
+
+        let (vertices, indices) = build_indexed_mesh(&data, polys);
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 3]);
+    }
+
+    fn signed_area_xy(points: &[[f32; 3]]) -> f32 {
+        (0..points.len())
+            .map(|i| {
+                let a = points[i];
+                let b = points[(i + 1) % points.len()];
+                a[0] * b[1] - b[0] * a[1]
+            })
+            .sum::<f32>()
+            * 0.5
+    }
+
+    /// Checks that the triangles cover exactly the polygon's area and all
+    /// share its winding.
+    fn assert_triangulates(polygon: &[[f32; 3]]) {
+        let triangles = triangulate_polygon(polygon);
+        assert_eq!(triangles.len(), polygon.len() - 2);
+
+        let polygon_area = signed_area_xy(polygon);
+        let mut total_area = 0.0;
+        for [a, b, c] in triangles {
+            let area = signed_area_xy(&[polygon[a], polygon[b], polygon[c]]);
+            assert!(area * polygon_area > 0.0, "triangle flipped winding");
+            total_area += area;
+        }
+        assert!((total_area - polygon_area).abs() < 1e-5);
+    }
+
+    #[test]
+    fn triangulates_quad() {
+        assert_triangulates(&[[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]]);
+    }
+
+    #[test]
+    fn triangulates_clockwise_ngon() {
+        let hexagon: Vec<[f32; 3]> = (0..6)
+            .map(|i| {
+                let angle = -(i as f32) * std::f32::consts::PI / 3.0;
+                [angle.cos(), angle.sin(), 0.0]
+            })
+            .collect();
+        assert_triangulates(&hexagon);
+    }
+
+    #[test]
+    fn triangulates_concave_polygon() {
+        // An arrow head whose reflex corner breaks a naive fan from vertex 0.
+        assert_triangulates(&[[0., 0., 0.], [2., 1., 0.], [0., 2., 0.], [1., 1., 0.]]);
+        // An L shape with its reflex corner mid-list.
+        assert_triangulates(&[
+            [0., 0., 0.],
+            [2., 0., 0.],
+            [2., 1., 0.],
+            [1., 1., 0.],
+            [1., 2., 0.],
+            [0., 2., 0.],
+        ]);
+    }
+
+    #[test]
+    fn triangulates_polygon_off_the_xy_plane() {
+        let quad = [[0., 0., 0.], [0., 0., 1.], [0., 1., 1.], [0., 1., 0.]];
+        assert_eq!(triangulate_polygon(&quad).len(), 2);
+    }
+
+    #[test]
+    fn missing_normals_respect_crease_angle() {
+        // A unit cube without normals or texture coordinates: every corner
+        // sits on a 90 degree crease, so each face keeps its own flat normal.
+        let src = b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv 0 0 1\nv 1 0 1\nv 1 1 1\nv 0 1 1\n\
+            f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 2 3 7 6\nf 3 4 8 7\nf 4 1 5 8\n";
+        let data = obj::ObjData::load_buf(&src[..]).unwrap();
+        let polys = &data.objects[0].groups[0].polys;
+
+        let (vertices, indices) = build_indexed_mesh(&data, polys);
+
+        assert_eq!(vertices.len(), 24);
+        assert_eq!(indices.len(), 36);
+        for vertex in vertices.iter() {
+            assert_eq!(vertex.tex_coord, [0.0, 0.0]);
+            assert_eq!(vertex.normal.iter().filter(|c| c.abs() == 1.0).count(), 1);
+        }
+    }
+
+    #[test]
+    fn missing_normals_are_smoothed_across_shallow_folds() {
+        // Two triangles folded by about 11 degrees along the shared 2-3 edge.
+        let src = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0.2\nvt 0 0\nf 1/1 2/1 3/1\nf 3/1 2/1 4/1\n";
+        let data = obj::ObjData::load_buf(&src[..]).unwrap();
+        let polys = &data.objects[0].groups[0].polys;
+
+        let (vertices, indices) = build_indexed_mesh(&data, polys);
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices.len(), 6);
+        let shared = vertices[indices[1] as usize].normal;
+        assert!(shared[2] > 0.0 && shared[2] < 1.0);
+        assert_eq!(shared, vertices[indices[2] as usize].normal);
+    }
+
+    #[test]
+    fn tangents_follow_the_u_direction() {
+        // A quad in the XY plane whose U axis runs along -X, so the tangent
+        // must point that way and stay orthogonal to the normal.
+        let src = b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+            vt 1 0\nvt 0 0\nvt 0 1\nvt 1 1\nvn 0 0 1\n\
+            f 1/1/1 2/2/1 3/3/1 4/4/1\n";
+        let data = obj::ObjData::load_buf(&src[..]).unwrap();
+        let polys = &data.objects[0].groups[0].polys;
+
+        let (vertices, _) = build_indexed_mesh(&data, polys);
+
+        for vertex in vertices.iter() {
+            let [x, y, z, w] = vertex.tangent;
+            assert!((x + 1.0).abs() < 1e-5 && y.abs() < 1e-5 && z.abs() < 1e-5);
+            assert!(w == 1.0 || w == -1.0);
+        }
+    }
+
+    #[test]
+    fn texture_coordinates_are_flipped_to_a_top_left_origin() {
+        let src = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0.25 0.25\nvn 0 0 1\n\
+            f 1/1/1 2/1/1 3/1/1\n";
+        let data = obj::ObjData::load_buf(&src[..]).unwrap();
+        let polys = &data.objects[0].groups[0].polys;
+
+        let (vertices, _) = build_indexed_mesh(&data, polys);
+
+        assert!(vertices
+            .iter()
+            .all(|vertex| vertex.tex_coord == [0.25, 0.75]));
+    }
+
+    #[test]
+    fn texture_options_are_stripped() {
+        assert_eq!(texture_file_name("textures/a.png"), "textures/a.png");
+        assert_eq!(
+            texture_file_name("-bm 0.5 textures/a b.png"),
+            "textures/a b.png"
+        );
+        assert_eq!(texture_file_name("-s 2 2 -clamp on a.png"), "a.png");
+        assert_eq!(texture_file_name("-o 0.5 a.png"), "a.png");
+    }
+
+    #[test]
+    fn clamp_option_selects_clamped_wrap() {
+        let mut material = obj::Material::new("clamped".to_owned());
+        assert_eq!(
+            SamplerSettings::from_mtl(&material),
+            SamplerSettings::default()
+        );
+
+        material.map_kd = Some("-clamp on -bm 1 decal.png".to_owned());
+        assert_eq!(
+            SamplerSettings::from_mtl(&material).wrap,
+            SamplerWrapFunction::Clamp
+        );
+
+        material.map_kd = Some("-clamp off decal.png".to_owned());
+        assert_eq!(
+            SamplerSettings::from_mtl(&material).wrap,
+            SamplerWrapFunction::Repeat
+        );
+    }
+
+    #[test]
+    fn quads_in_obj_become_two_triangles() {
+        let src =
+            b"v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvt 0 0\nvn 0 0 1\nf 1/1/1 2/1/1 3/1/1 4/1/1\n";
+        let data = obj::ObjData::load_buf(&src[..]).unwrap();
+        let polys = &data.objects[0].groups[0].polys;
+
+        let (vertices, indices) = build_indexed_mesh(&data, polys);
+
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices.len(), 6);
+    }
+
+    fn load(path: &Path) -> ModelData {
+        ModelData::load(
+            path.to_str().unwrap(),
+            &[],
+            &HashSet::new(),
+            &LoadProgress::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn loads_obj_without_a_display() {
+        let data = load(&scratch_crate("model_data_obj"));
+
+        assert_eq!(data.meshes.len(), 1);
+        assert_eq!(data.meshes[0].material.diffuse_color, [0.8; 3]);
+        assert_eq!(data.meshes[0].material.illumination_model, 2);
+        assert!(data.images.is_empty());
+        assert!(data.lights.is_empty());
+    }
+
+    #[test]
+    fn groups_without_a_known_material_fall_back_to_the_default() {
+        let dir = scratch_dir_with(
+            "model_data_fallback",
+            &[
+                (
+                    "mixed.obj",
+                    b"mtllib mixed.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\ng plain\nf 1 2 3\n\
+                    g red\nusemtl red\nf 1 2 3\ng unknown\nusemtl unknown\nf 1 2 3\n",
+                ),
+                ("mixed.mtl", b"newmtl red\nKd 1 0 0\n"),
+            ],
+        );
+
+        let data = load(&dir.join("mixed.obj"));
+        let diffuse_colors: Vec<[f32; 3]> = data
+            .meshes
+            .iter()
+            .map(|mesh| mesh.material.diffuse_color)
+            .collect();
+
+        assert_eq!(diffuse_colors, vec![[0.8; 3], [1.0, 0.0, 0.0], [0.8; 3]]);
+        assert_eq!(data.meshes[2].material, MaterialData::default());
+    }
+
+    #[test]
+    fn material_maps_resolve_to_decoded_images() {
+        let mut png = vec![];
+        image::RgbaImage::from_pixel(2, 1, image::Rgba([10, 20, 30, 255]))
+            .write_to(
+                &mut std::io::Cursor::new(&mut png),
+                image::ImageOutputFormat::Png,
+            )
+            .unwrap();
+        let dir = scratch_dir_with(
+            "model_data_maps",
+            &[
+                (
+                    "wall.obj",
+                    b"mtllib wall.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl wall\nf 1 2 3\n",
+                ),
+                (
+                    "wall.mtl",
+                    b"newmtl wall\nKd 1 1 1\nTr 0.25\nmap_Kd -bm 1 Textures\\Brick.png\nmap_bump missing.png\n",
+                ),
+                ("textures/brick.png", &png),
+            ],
+        );
+
+        let data = load(&dir.join("wall.obj"));
+        let material = &data.meshes[0].material;
+
+        assert_eq!(material.dissolve, 0.75);
+        let diffuse_map = material.diffuse_map.as_ref().unwrap();
+        assert_eq!(
+            diffuse_map,
+            &canonical_path(&dir.join("textures/brick.png"))
+        );
+        let image = data.images[diffuse_map].as_ref().unwrap();
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(&image.pixels[..4], &[10, 20, 30, 255]);
+
+        // A map that can't be found keeps its name and fails to decode.
+        let normal_map = material.normal_map.as_ref().unwrap();
+        assert_eq!(normal_map, Path::new("missing.png"));
+        assert!(data.images[normal_map].is_none());
+    }
+
+    #[test]
+    fn dissolve_maps_alone_are_masks() {
+        let dir = scratch_dir_with(
+            "model_data_alpha_modes",
+            &[
+                (
+                    "leaves.obj",
+                    b"mtllib leaves.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\ng leaf\nusemtl leaf\nf 1 2 3\n\
+                    g glass\nusemtl glass\nf 1 2 3\n",
+                ),
+                (
+                    "leaves.mtl",
+                    b"newmtl leaf\nmap_d leaf_mask.png\nnewmtl glass\nd 0.5\n",
+                ),
+            ],
+        );
+
+        let data = load(&dir.join("leaves.obj"));
+
+        assert_eq!(
+            data.meshes[0].material.alpha_mode,
+            AlphaMode::Mask(MTL_ALPHA_CUTOFF)
+        );
+        assert_eq!(data.meshes[1].material.alpha_mode, AlphaMode::Blend);
+    }
+
+    #[test]
+    fn unsupported_mtl_statements_are_skipped() {
+        let dir = scratch_dir_with(
+            "model_data_mtl_statements",
+            &[
+                (
+                    "lamp.obj",
+                    b"mtllib lamp.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl lamp\nf 1 2 3\n",
+                ),
+                (
+                    "lamp.mtl",
+                    b"newmtl lamp\nKd 1 1 1\nPr 0.5\nPm 1\nmap_Pr rough.png\n\
+                    map_Ke glow.png\nnorm bumps.png\n",
+                ),
+            ],
+        );
+
+        let data = load(&dir.join("lamp.obj"));
+        let material = &data.meshes[0].material;
+
+        assert_eq!(material.diffuse_color, [1.0; 3]);
+        assert_eq!(
+            material.emissive_map.as_deref(),
+            Some(Path::new("glow.png"))
+        );
+        assert_eq!(material.normal_map.as_deref(), Some(Path::new("bumps.png")));
+    }
+
+    #[test]
+    fn cached_maps_are_not_decoded_again() {
+        let dir = scratch_dir_with(
+            "model_data_cached",
+            &[
+                (
+                    "wall.obj",
+                    b"mtllib wall.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl wall\nf 1 2 3\n",
+                ),
+                ("wall.mtl", b"newmtl wall\nmap_Kd brick.png\n"),
+                ("brick.png", b""),
+            ],
+        );
+        let cached = HashSet::from([canonical_path(&dir.join("brick.png"))]);
+
+        let data = ModelData::load(
+            dir.join("wall.obj").to_str().unwrap(),
+            &[],
+            &cached,
+            &LoadProgress::default(),
+        )
+        .unwrap();
+
+        assert!(data.images.is_empty());
+    }
+
+    #[test]
+    fn loads_gltf_without_a_display() {
+        let gltf = br#"{
+            "asset": { "version": "2.0" },
+            "extensionsUsed": ["KHR_lights_punctual"],
+            "extensions": {
+                "KHR_lights_punctual": {
+                    "lights": [{ "type": "point", "color": [1, 0.5, 0], "intensity": 3 }]
+                }
+            },
+            "scene": 0,
+            "scenes": [{ "nodes": [0] }],
+            "nodes": [
+                { "translation": [0, 0, 5], "mesh": 0, "children": [1] },
+                { "translation": [0, 2, 0], "extensions": { "KHR_lights_punctual": { "light": 0 } } }
+            ],
+            "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+            "materials": [{
+                "alphaMode": "MASK",
+                "alphaCutoff": 0.25,
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": [0.5, 0.25, 1, 0.5],
+                    "metallicFactor": 0.75,
+                    "roughnessFactor": 0.5
+                }
+            }],
+            "buffers": [{
+                "byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+            }],
+            "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+            "accessors": [{
+                "bufferView": 0,
+                "componentType": 5126,
+                "count": 3,
+                "type": "VEC3",
+                "min": [0, 0, 0],
+                "max": [1, 1, 0]
+            }]
+        }"#;
+        let dir = scratch_dir_with("model_data_gltf", &[("triangle.gltf", gltf)]);
+
+        let data = load(&dir.join("triangle.gltf"));
+
+        assert_eq!(data.meshes.len(), 1);
+        let mesh = &data.meshes[0];
+        let mut positions: Vec<[f32; 3]> =
+            mesh.vertices.iter().map(|vertex| vertex.position).collect();
+        positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(
+            positions,
+            vec![[0.0, 0.0, 5.0], [0.0, 1.0, 5.0], [1.0, 0.0, 5.0]]
+        );
+        assert_eq!(mesh.material.metallic_roughness, Some([0.75, 0.5]));
+        assert_eq!(mesh.material.diffuse_color, [0.5, 0.25, 1.0]);
+        assert_eq!(mesh.material.dissolve, 0.5);
+        assert_eq!(mesh.material.alpha_mode, AlphaMode::Mask(0.25));
+
+        assert_eq!(data.lights.len(), 1);
+        assert_eq!(data.lights[0].get_position(), &[0.0, 2.0, 5.0]);
+        assert_eq!(data.lights[0].get_color(), &[1.0, 0.5, 0.0]);
+    }
+}
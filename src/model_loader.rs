@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+
+use crate::load_error::ModelLoadError;
+use crate::model_data::ModelData;
+use crate::texture_cache::TextureCache;
+
+/// Counts finished loading steps (meshes built, images decoded) against the
+/// steps known so far. Shared between the loader and the UI.
+#[derive(Default)]
+pub struct LoadProgress {
+    completed: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl LoadProgress {
+    pub fn add_work(&self, steps: usize) {
+        self.total.fetch_add(steps, Ordering::Relaxed);
+    }
+
+    pub fn complete_step(&self) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Fraction of the known work that is done, from 0 to 1.
+    pub fn get_fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+        if total == 0 {
+            return 0.0;
+        }
+        self.completed.load(Ordering::Relaxed) as f32 / total as f32
+    }
+}
+
+/// A model whose CPU-side data is being decoded on a background thread. Once
+/// it is done, the data is handed back to the main thread for upload with
+/// `Model::upload`, since only that thread may touch GL.
+pub struct PendingModel {
+    path: String,
+    progress: Arc<LoadProgress>,
+    receiver: mpsc::Receiver<Result<ModelData, ModelLoadError>>,
+}
+
+impl PendingModel {
+    /// Starts loading `path`, skipping images `texture_cache` already holds.
+    pub fn spawn(path: &str, search_paths: &[PathBuf], texture_cache: &TextureCache) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let progress = Arc::new(LoadProgress::default());
+
+        let cached = texture_cache.get_cached_paths();
+        let worker_path = path.to_owned();
+        let search_paths = search_paths.to_vec();
+        let worker_progress = progress.clone();
+        std::thread::spawn(move || {
+            let data = ModelData::load(&worker_path, &search_paths, &cached, &worker_progress);
+            // The receiver is gone only if the model was abandoned.
+            let _ = sender.send(data);
+        });
+
+        Self {
+            path: path.to_owned(),
+            progress,
+            receiver,
+        }
+    }
+
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_progress(&self) -> f32 {
+        self.progress.get_fraction()
+    }
+
+    /// Returns the loaded data, or the reason loading failed, once the worker
+    /// is done; `None` while it is still running.
+    pub fn try_take(&self) -> Option<Result<ModelData, ModelLoadError>> {
+        match self.receiver.try_recv() {
+            Ok(data) => Some(data),
+            Err(mpsc::TryRecvError::Empty) => None,
+            Err(mpsc::TryRecvError::Disconnected) => Some(Err(ModelLoadError::LoaderPanicked {
+                path: self.path.clone().into(),
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_crate;
+
+    #[test]
+    fn loads_in_the_background() {
+        let source = scratch_crate("model_loader_background");
+        let pending = PendingModel::spawn(source.to_str().unwrap(), &[], &TextureCache::new());
+
+        let data = loop {
+            if let Some(data) = pending.try_take() {
+                break data.unwrap();
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        };
+
+        assert_eq!(pending.get_progress(), 1.0);
+        assert_eq!(data.meshes.len(), 1);
+        assert!(!data.meshes[0].indices.is_empty());
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use cgmath::{ElementWise, InnerSpace, Vector3};
+
+use crate::model_data::{
+    generate_indexed_tangents, MaterialData, MeshData, ModelData, SamplerSettings, Vertex,
+};
+
+/// Fewest segments around a curved surface, and rings or sides across one,
+/// that still enclose any volume.
+const MIN_SEGMENTS: u32 = 3;
+const MIN_RINGS: u32 = 2;
+const MIN_SIDES: u32 = 3;
+
+/// Generated meshes in meters, centered on the origin with Y up. Curved
+/// surfaces are split into `segments` around the Y axis and `rings` or
+/// `sides` across it, raised to the fewest that make a closed surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Primitive {
+    Sphere {
+        radius: f32,
+        segments: u32,
+        rings: u32,
+    },
+    /// An axis-aligned box with the given edge lengths.
+    Cube { size: Vector3<f32> },
+    /// A rectangle in the XZ plane facing +Y.
+    Plane { width: f32, depth: f32 },
+    /// A capped cylinder along Y.
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    /// A capped cone along Y with its apex at the top.
+    Cone {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    /// A ring around Y; `major_radius` runs to the middle of the tube.
+    Torus {
+        major_radius: f32,
+        minor_radius: f32,
+        segments: u32,
+        sides: u32,
+    },
+}
+
+impl Primitive {
+    /// Builds the welded vertices and counter-clockwise triangle list, with
+    /// texture coordinates running 0 to 1 across each surface.
+    pub fn build(&self) -> (Vec<Vertex>, Vec<u32>) {
+        let mut mesh = MeshBuilder::default();
+        match *self {
+            Self::Sphere {
+                radius,
+                segments,
+                rings,
+            } => mesh.grid(segments.max(MIN_SEGMENTS), rings.max(MIN_RINGS), |u, v| {
+                let normal = spherical(TAU * u, PI * (1.0 - v));
+                (normal * radius, normal)
+            }),
+            Self::Cube { size } => {
+                let half = size * 0.5;
+                for (normal, u_axis, v_axis) in [
+                    (Vector3::unit_x(), -Vector3::unit_z(), Vector3::unit_y()),
+                    (-Vector3::unit_x(), Vector3::unit_z(), Vector3::unit_y()),
+                    (Vector3::unit_y(), Vector3::unit_x(), -Vector3::unit_z()),
+                    (-Vector3::unit_y(), Vector3::unit_x(), Vector3::unit_z()),
+                    (Vector3::unit_z(), Vector3::unit_x(), Vector3::unit_y()),
+                    (-Vector3::unit_z(), -Vector3::unit_x(), Vector3::unit_y()),
+                ] {
+                    mesh.grid(1, 1, |u, v| {
+                        let point = normal + u_axis * (u * 2.0 - 1.0) + v_axis * (v * 2.0 - 1.0);
+                        (point.mul_element_wise(half), normal)
+                    });
+                }
+            }
+            Self::Plane { width, depth } => mesh.grid(1, 1, |u, v| {
+                let position = Vector3::new((u - 0.5) * width, 0.0, (0.5 - v) * depth);
+                (position, Vector3::unit_y())
+            }),
+            Self::Cylinder {
+                radius,
+                height,
+                segments,
+            } => {
+                let segments = segments.max(MIN_SEGMENTS);
+                mesh.grid(segments, 1, |u, v| {
+                    let normal = spherical(TAU * u, PI * 0.5);
+                    let position = normal * radius + Vector3::unit_y() * (v - 0.5) * height;
+                    (position, normal)
+                });
+                mesh.disk(radius, height * 0.5, segments, true);
+                mesh.disk(radius, -height * 0.5, segments, false);
+            }
+            Self::Cone {
+                radius,
+                height,
+                segments,
+            } => {
+                let segments = segments.max(MIN_SEGMENTS);
+                mesh.grid(segments, 1, |u, v| {
+                    let around = spherical(TAU * u, PI * 0.5);
+                    let position =
+                        around * radius * (1.0 - v) + Vector3::unit_y() * (v - 0.5) * height;
+                    let normal = (around * height + Vector3::unit_y() * radius).normalize();
+                    (position, normal)
+                });
+                mesh.disk(radius, -height * 0.5, segments, false);
+            }
+            Self::Torus {
+                major_radius,
+                minor_radius,
+                segments,
+                sides,
+            } => mesh.grid(segments.max(MIN_SEGMENTS), sides.max(MIN_SIDES), |u, v| {
+                let around = spherical(TAU * u, PI * 0.5);
+                let (sin, cos) = (TAU * v).sin_cos();
+                let normal = around * cos + Vector3::unit_y() * sin;
+                (around * major_radius + normal * minor_radius, normal)
+            }),
+        }
+
+        let MeshBuilder {
+            mut vertices,
+            indices,
+        } = mesh;
+        generate_indexed_tangents(&mut vertices, &indices);
+        (vertices, indices)
+    }
+
+    /// The primitive as a mesh drawn with `material`, or `None` when it is
+    /// too small to have any triangles, as with a zero radius.
+    pub fn build_mesh(&self, material: MaterialData) -> Option<MeshData> {
+        let (vertices, indices) = self.build();
+        MeshData::new(vertices, indices, material, SamplerSettings::default())
+    }
+}
+
+/// Model data for `meshes`, ready for `Model::upload`. `name` stands in for
+/// the file path in error messages.
+pub fn model_data(name: &str, meshes: Vec<MeshData>) -> ModelData {
+    ModelData {
+        path: name.into(),
+        sources: vec![],
+        meshes,
+        images: HashMap::new(),
+        lights: vec![],
+        scale: 1.0,
+    }
+}
+
+/// The unit vector at `azimuth` around Y, measured from +Z towards +X, and
+/// `polar` down from +Y.
+fn spherical(azimuth: f32, polar: f32) -> Vector3<f32> {
+    let (sin_azimuth, cos_azimuth) = azimuth.sin_cos();
+    let (sin_polar, cos_polar) = polar.sin_cos();
+    Vector3::new(sin_polar * sin_azimuth, cos_polar, sin_polar * cos_azimuth)
+}
+
+#[derive(Default)]
+struct MeshBuilder {
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    /// Adds a `columns` by `rows` grid of quads over a surface mapping (u, v)
+    /// in 0..=1 to a position and normal. The surface must be oriented so
+    /// that its u and v derivatives cross to the normal's side, which makes
+    /// the triangles counter-clockwise seen from outside. Triangles collapsed
+    /// to a line, as at a pole or an apex, are left out.
+    fn grid<F>(&mut self, columns: u32, rows: u32, surface: F)
+    where
+        F: Fn(f32, f32) -> (Vector3<f32>, Vector3<f32>),
+    {
+        self.surface(columns, rows, |u, v| {
+            let (position, normal) = surface(u, v);
+            (position, normal, [u, v])
+        });
+    }
+
+    /// Adds a flat disk at `height` facing +Y, or -Y unless `up`, with its
+    /// texture mapped straight down onto it. A disk without area adds
+    /// nothing.
+    fn disk(&mut self, radius: f32, height: f32, segments: u32, up: bool) {
+        if radius <= 0.0 {
+            return;
+        }
+        let (normal, sign) = if up {
+            (Vector3::unit_y(), 1.0)
+        } else {
+            (-Vector3::unit_y(), -1.0)
+        };
+        self.surface(segments, 1, |u, v| {
+            // Around the rim first, then in to the center, with the sweep
+            // reversed underneath to keep the winding outward.
+            let around = spherical(sign * TAU * u, PI * 0.5);
+            let position = around * radius * (1.0 - v) + Vector3::unit_y() * height;
+            let tex_coord = [
+                0.5 + position.x / (2.0 * radius),
+                0.5 - sign * position.z / (2.0 * radius),
+            ];
+            (position, normal, tex_coord)
+        });
+    }
+
+    fn surface<F>(&mut self, columns: u32, rows: u32, surface: F)
+    where
+        F: Fn(f32, f32) -> (Vector3<f32>, Vector3<f32>, [f32; 2]),
+    {
+        let first = self.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                let u = column as f32 / columns as f32;
+                let v = row as f32 / rows as f32;
+                let (position, normal, tex_coord) = surface(u, v);
+                self.vertices.push(Vertex {
+                    position: position.into(),
+                    tex_coord,
+                    normal: normal.into(),
+                    tangent: [1.0, 0.0, 0.0, 1.0],
+                });
+            }
+        }
+
+        let index = |column: u32, row: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let corners = [
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ];
+                for triangle in [[0, 1, 2], [0, 2, 3]].map(|triangle| triangle.map(|i| corners[i]))
+                {
+                    if !self.is_degenerate(triangle) {
+                        self.indices.extend(triangle);
+                    }
+                }
+            }
+        }
+    }
+
+    fn is_degenerate(&self, triangle: [u32; 3]) -> bool {
+        let [a, b, c] = triangle.map(|index| Vector3::from(self.vertices[index as usize].position));
+        (b - a).cross(c - a).magnitude2() < 1e-12
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> Vec<Primitive> {
+        vec![
+            Primitive::Sphere {
+                radius: 2.0,
+                segments: 16,
+                rings: 8,
+            },
+            Primitive::Cube {
+                size: Vector3::new(1.0, 2.0, 3.0),
+            },
+            Primitive::Plane {
+                width: 2.0,
+                depth: 1.0,
+            },
+            Primitive::Cylinder {
+                radius: 0.5,
+                height: 2.0,
+                segments: 12,
+            },
+            Primitive::Cone {
+                radius: 0.5,
+                height: 1.0,
+                segments: 12,
+            },
+            Primitive::Torus {
+                major_radius: 1.0,
+                minor_radius: 0.25,
+                segments: 16,
+                sides: 8,
+            },
+        ]
+    }
+
+    #[test]
+    fn triangles_wind_counter_clockwise_around_their_normals() {
+        for primitive in all() {
+            let (vertices, indices) = primitive.build();
+            assert!(!indices.is_empty());
+
+            for triangle in indices.chunks(3) {
+                let [a, b, c] = [0, 1, 2].map(|i| &vertices[triangle[i] as usize]);
+                let face_normal = (Vector3::from(b.position) - Vector3::from(a.position))
+                    .cross(Vector3::from(c.position) - Vector3::from(a.position));
+                let vertex_normal =
+                    Vector3::from(a.normal) + Vector3::from(b.normal) + Vector3::from(c.normal);
+                assert!(
+                    face_normal.dot(vertex_normal) > 0.0,
+                    "{:?} has an inward triangle",
+                    primitive
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn vertices_have_unit_normals_tangents_and_unit_square_uvs() {
+        for primitive in all() {
+            let (vertices, _) = primitive.build();
+
+            for vertex in vertices {
+                let normal = Vector3::from(vertex.normal);
+                let [x, y, z, w] = vertex.tangent;
+                let tangent = Vector3::new(x, y, z);
+                assert!((normal.magnitude() - 1.0).abs() < 1e-5, "{:?}", primitive);
+                assert!(normal.dot(tangent).abs() < 1e-3, "{:?}", primitive);
+                assert!(w == 1.0 || w == -1.0);
+                assert!(vertex
+                    .tex_coord
+                    .iter()
+                    .all(|&t| (-1e-5..=1.0 + 1e-5).contains(&t)));
+            }
+        }
+    }
+
+    #[test]
+    fn shapes_have_the_requested_size() {
+        let (vertices, _) = all()[0].build();
+        for vertex in vertices {
+            assert!((Vector3::from(vertex.position).magnitude() - 2.0).abs() < 1e-5);
+        }
+
+        let cube = all()[1].build_mesh(MaterialData::default()).unwrap();
+        assert_eq!(cube.bounds.get_size(), Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(cube.indices.len(), 36);
+
+        let torus = all()[5].build_mesh(MaterialData::default()).unwrap();
+        assert!((torus.bounds.get_size() - Vector3::new(2.5, 0.5, 2.5)).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn degenerate_parameters_are_raised_or_rejected() {
+        let sphere = Primitive::Sphere {
+            radius: 1.0,
+            segments: 0,
+            rings: 0,
+        };
+        let mesh = sphere.build_mesh(MaterialData::default()).unwrap();
+        assert!(mesh
+            .vertices
+            .iter()
+            .all(|vertex| vertex.tex_coord.iter().all(|t| t.is_finite())));
+
+        let cylinder = Primitive::Cylinder {
+            radius: 0.0,
+            height: 1.0,
+            segments: 8,
+        };
+        assert!(cylinder.build_mesh(MaterialData::default()).is_none());
+    }
+}
@@ -1,15 +1,44 @@
+use cgmath::InnerSpace;
+use glium::backend::Facade;
 use glium::uniforms::SamplerWrapFunction;
 
 use crate::{
-    camera::Camera, model::Model, model_render_system::ModelRenderSystem,
+    bounds::Frustum,
+    camera::{Camera, Projection, ProjectionMode},
+    model::MeshObject,
+    model_data::{AlphaMode, SamplerSettings},
+    model_render_system::ModelRenderSystem,
+    scene::Scene,
     shadow_render_system::ShadowRenderSystem,
+    transform,
 };
 
+const DEFAULT_ANISOTROPY: u16 = 16;
+
+// glClipControl arguments, which glium doesn't wrap.
+const GL_LOWER_LEFT: u32 = 0x8CA1;
+const GL_NEGATIVE_ONE_TO_ONE: u32 = 0x935E;
+const GL_ZERO_TO_ONE: u32 = 0x935F;
+
+type ClipControlFn = extern "system" fn(origin: u32, depth: u32);
+
+/// Offscreen color and floating-point depth for reverse-Z, since the window's
+/// own depth buffer is 24-bit fixed point.
+struct SceneTarget {
+    color: glium::texture::Texture2d,
+    depth: glium::texture::DepthTexture2d,
+}
+
 pub struct Renderer {
     model_render_system: ModelRenderSystem,
     shadow_render_system: ShadowRenderSystem,
     scene_draw_params: glium::DrawParameters<'static>,
+    reverse_z_draw_params: glium::DrawParameters<'static>,
     shadow_draw_params: glium::DrawParameters<'static>,
+    anisotropy: u16,
+    clip_control: Option<ClipControlFn>,
+    present_program: glium::Program,
+    scene_target: Option<SceneTarget>,
 }
 
 impl Renderer {
@@ -25,6 +54,15 @@ impl Renderer {
             ..Default::default()
         };
 
+        let reverse_z_draw_params = glium::DrawParameters {
+            depth: glium::Depth {
+                test: glium::draw_parameters::DepthTest::IfMore,
+                write: true,
+                ..Default::default()
+            },
+            ..scene_draw_params.clone()
+        };
+
         let shadow_draw_params = glium::DrawParameters {
             depth: glium::Depth {
                 test: glium::draw_parameters::DepthTest::IfLess,
@@ -37,14 +75,109 @@ impl Renderer {
 
         let shadow_render_system = ShadowRenderSystem::new(display);
 
+        let clip_control = {
+            let address = display.gl_window().get_proc_address("glClipControl");
+            // SAFETY: a non-null address is the driver's glClipControl, which
+            // has this signature.
+            (!address.is_null()).then(|| unsafe {
+                std::mem::transmute::<*const std::ffi::c_void, ClipControlFn>(address)
+            })
+        };
+
+        let vertex_shader_src = std::fs::read_to_string("./present.vert").unwrap();
+        let fragment_shader_src = std::fs::read_to_string("./present.frag").unwrap();
+        let present_program =
+            glium::Program::from_source(display, &vertex_shader_src, &fragment_shader_src, None)
+                .unwrap();
+
         Self {
             model_render_system,
             scene_draw_params,
+            reverse_z_draw_params,
             shadow_draw_params,
             shadow_render_system,
+            anisotropy: DEFAULT_ANISOTROPY,
+            clip_control,
+            present_program,
+            scene_target: None,
         }
     }
 
+    /// Whether the driver can do reverse-Z, which needs glClipControl
+    /// (OpenGL 4.5). Cameras asking for it get the standard depth otherwise.
+    pub fn supports_reverse_z(&self) -> bool {
+        self.clip_control.is_some()
+    }
+
+    /// Switches clip space depth between -w..w and 0..w.
+    fn set_clip_depth_zero_to_one(&self, display: &glium::Display, zero_to_one: bool) {
+        if let Some(clip_control) = self.clip_control {
+            let depth = if zero_to_one {
+                GL_ZERO_TO_ONE
+            } else {
+                GL_NEGATIVE_ONE_TO_ONE
+            };
+            // SAFETY: glium doesn't track the clip control state, so changing
+            // it behind its back is fine as long as it is put back.
+            unsafe {
+                display
+                    .get_context()
+                    .exec_in_context(|| clip_control(GL_LOWER_LEFT, depth))
+            }
+        }
+    }
+
+    /// Creates the offscreen target for reverse-Z, or recreates it when the
+    /// window size changed.
+    fn update_scene_target(&mut self, display: &glium::Display, (width, height): (u32, u32)) {
+        let outdated = self
+            .scene_target
+            .as_ref()
+            .is_none_or(|target| target.color.dimensions() != (width, height));
+        if outdated {
+            self.scene_target = Some(SceneTarget {
+                color: glium::texture::Texture2d::empty_with_format(
+                    display,
+                    glium::texture::UncompressedFloatFormat::F16F16F16F16,
+                    glium::texture::MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                )
+                .unwrap(),
+                depth: glium::texture::DepthTexture2d::empty_with_format(
+                    display,
+                    glium::texture::DepthFormat::F32,
+                    glium::texture::MipmapsOption::NoMipmap,
+                    width,
+                    height,
+                )
+                .unwrap(),
+            });
+        }
+    }
+
+    pub fn get_anisotropy(&self) -> u16 {
+        self.anisotropy
+    }
+
+    /// Sets the anisotropic filtering level for material maps; 1 disables
+    /// it, and drivers clamp values above what the hardware supports.
+    pub fn set_anisotropy(&mut self, anisotropy: u16) {
+        self.anisotropy = anisotropy.max(1);
+    }
+
+    fn material_sampler<'t, T>(
+        &self,
+        texture: &'t T,
+        settings: &SamplerSettings,
+    ) -> glium::uniforms::Sampler<'t, T> {
+        glium::uniforms::Sampler::new(texture)
+            .wrap_function(settings.wrap)
+            .minify_filter(settings.minify)
+            .magnify_filter(settings.magnify)
+            .anisotropy(self.anisotropy)
+    }
+
     pub fn get_drawable_shadow_texture(&self) -> std::rc::Rc<glium::texture::SrgbTexture2d> {
         self.shadow_render_system.get_drawable_shadow_texture()
     }
@@ -52,7 +185,7 @@ impl Renderer {
     pub fn render_shadows(
         &self,
         display: &glium::Display,
-        models: &Vec<Model>,
+        scene: &Scene,
         light_position: &[f32; 3],
     ) {
         use glium::Surface;
@@ -71,19 +204,18 @@ impl Renderer {
                 .get_view_matrix(light_position.into()))
         .into();
 
-        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
-
-        for model in models {
+        for (model, model_matrix) in scene.get_models() {
+            let model_transform: [[f32; 4]; 4] = model_matrix.into();
             for mesh_object in model.get_mesh_objects() {
                 let uniforms = &uniform! {
-                    model: model.get_transform(),
+                    model: model_transform,
                     view_proj: view_proj,
                 };
 
                 target
                     .draw(
                         mesh_object.get_vertices(),
-                        &indices,
+                        mesh_object.get_indices(),
                         self.shadow_render_system.get_shader_program(),
                         uniforms,
                         &self.shadow_draw_params,
@@ -93,18 +225,96 @@ impl Renderer {
         }
     }
 
+    /// Clears `target` to `clear_color` and draws the scene. A reverse-Z
+    /// camera is drawn offscreen with a floating-point depth buffer and then
+    /// copied over.
+    #[allow(clippy::too_many_arguments)]
     pub fn render_scene(
-        &self,
+        &mut self,
+        display: &glium::Display,
         target: &mut glium::Frame,
+        clear_color: (f32, f32, f32, f32),
         camera: &Camera,
-        models: &Vec<Model>,
+        scene: &Scene,
         light_position: &[f32; 3],
+        light_color: &[f32; 3],
     ) {
         use glium::Surface;
-        let view_proj: [[f32; 4]; 4] =
-            (camera.get_projection_matrix() * camera.get_view_matrix()).into();
 
-        let indices = glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList);
+        if !(camera.get_projection().is_reverse_z() && self.supports_reverse_z()) {
+            // Without glClipControl a reverse-Z camera falls back to its
+            // standard projection, which the window's depth buffer can hold.
+            let view_proj_matrix =
+                camera.get_projection().calc_standard_matrix() * camera.get_view_matrix();
+            target.clear_color_and_depth(clear_color, 1.0);
+            let frustum = Frustum::from_matrix(&view_proj_matrix);
+            self.draw_scene(
+                target,
+                &self.scene_draw_params,
+                &view_proj_matrix,
+                &frustum,
+                camera,
+                scene,
+                light_position,
+                light_color,
+            );
+            return;
+        }
+
+        let dimensions = target.get_dimensions();
+        self.update_scene_target(display, dimensions);
+        let scene_target = self.scene_target.as_ref().unwrap();
+        let mut framebuffer = glium::framebuffer::SimpleFrameBuffer::with_depth_buffer(
+            display,
+            &scene_target.color,
+            &scene_target.depth,
+        )
+        .unwrap();
+        framebuffer.clear_color_and_depth(clear_color, 0.0);
+
+        let view_proj_matrix = camera.get_projection_matrix() * camera.get_view_matrix();
+        let frustum = Frustum::from_reverse_z_matrix(&view_proj_matrix);
+        self.set_clip_depth_zero_to_one(display, true);
+        self.draw_scene(
+            &mut framebuffer,
+            &self.reverse_z_draw_params,
+            &view_proj_matrix,
+            &frustum,
+            camera,
+            scene,
+            light_position,
+            light_color,
+        );
+        self.set_clip_depth_zero_to_one(display, false);
+
+        // Only color is copied over, so whatever draws into the window next
+        // gets a cleared depth buffer rather than last frame's.
+        target.clear_depth(1.0);
+        target
+            .draw(
+                glium::vertex::EmptyVertexAttributes { len: 3 },
+                glium::index::NoIndices(glium::index::PrimitiveType::TrianglesList),
+                &self.present_program,
+                &uniform! { scene: &scene_target.color },
+                &Default::default(),
+            )
+            .unwrap();
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_scene<S: glium::Surface>(
+        &self,
+        target: &mut S,
+        draw_params: &glium::DrawParameters,
+        view_proj_matrix: &cgmath::Matrix4<f32>,
+        frustum: &Frustum,
+        camera: &Camera,
+        scene: &Scene,
+        light_position: &[f32; 3],
+        light_color: &[f32; 3],
+    ) {
+        let view_proj: [[f32; 4]; 4] = (*view_proj_matrix).into();
+
         let shadow_map =
             glium::uniforms::Sampler::new(self.shadow_render_system.get_shadow_texture())
                 .wrap_function(SamplerWrapFunction::Clamp)
@@ -131,39 +341,152 @@ impl Renderer {
         )
         .into();
 
-        let frustum_size = 2.0 * 0.1 * (45.0f32 * 0.5).tan() * camera.get_aspect_ratio();
+        let frustum_size = shadow_frustum_size(camera.get_projection());
+
+        let view_position: [f32; 3] = (*camera.get_view_position()).into();
 
         //println!("texel size {:?} bias {:?}", texel_size, shadow_bias);
 
-        for model in models {
+        let mut draw = |mesh_object: &MeshObject,
+                        model_transform: [[f32; 4]; 4],
+                        normal_matrix: [[f32; 3]; 3],
+                        draw_params: &glium::DrawParameters| {
+            let material = mesh_object.get_material();
+            let settings = mesh_object.get_sampler_settings();
+            let [metallic, roughness] = material.get_metallic_roughness().unwrap_or([0.0; 2]);
+            let maps = material.get_map_flags();
+            let (alpha_mode, alpha_cutoff) = match material.get_alpha_mode() {
+                AlphaMode::Opaque => (0, 0.0),
+                AlphaMode::Mask(cutoff) => (1, cutoff),
+                AlphaMode::Blend => (2, 0.0),
+            };
+            let uniforms = &uniform! {
+                model: model_transform,
+                normalMatrix: normal_matrix,
+                lightColor: *light_color,
+                ambientIntensity: 0.1f32,
+                lightPosition: *light_position,
+                view_proj: view_proj,
+                viewPosition: view_position,
+                tex: self.material_sampler(material.get_diffuse_texture(), settings),
+                ambientMap: self.material_sampler(material.get_ambient_texture(), settings),
+                specularMap: self.material_sampler(material.get_specular_texture(), settings),
+                emissiveMap: self.material_sampler(material.get_emissive_texture(), settings),
+                shininessMap: self.material_sampler(material.get_shininess_texture(), settings),
+                dissolveMap: self.material_sampler(material.get_dissolve_texture(), settings),
+                normalMap: self.material_sampler(material.get_normal_texture(), settings),
+                metallicRoughnessMap: self.material_sampler(material.get_metallic_roughness_texture(), settings),
+                shadowMap: shadow_map,
+                light_space_matrix: light_matrix,
+                texelSize: texel_size,
+                frustumSize: frustum_size,
+                distribution: self.shadow_render_system.get_poisson_disk_texture(),
+                ambientColor: *material.get_ambient_color(),
+                diffuseColor: *material.get_diffuse_color(),
+                specularColor: *material.get_specular_color(),
+                emissiveColor: *material.get_emissive_color(),
+                transmissionFilter: *material.get_transmission_filter(),
+                shininess: material.get_shininess(),
+                opticalDensity: material.get_optical_density(),
+                dissolve: material.get_dissolve(),
+                alphaMode: alpha_mode,
+                alphaCutoff: alpha_cutoff,
+                illuminationModel: material.get_illumination_model(),
+                metallicRoughness: material.get_metallic_roughness().is_some(),
+                metallic: metallic,
+                roughness: roughness,
+                hasDiffuseMap: maps.diffuse,
+                hasAmbientMap: maps.ambient,
+                hasSpecularMap: maps.specular,
+                hasEmissiveMap: maps.emissive,
+                hasShininessMap: maps.shininess,
+                hasDissolveMap: maps.dissolve,
+                hasNormalMap: maps.normal,
+                hasMetallicRoughnessMap: maps.metallic_roughness
+            };
+
+            target
+                .draw(
+                    mesh_object.get_vertices(),
+                    mesh_object.get_indices(),
+                    self.model_render_system.get_shader_program(),
+                    uniforms,
+                    draw_params,
+                )
+                .unwrap();
+        };
+
+        // Opaque meshes go first, writing depth. See-through ones are then
+        // blended over them farthest first, testing depth without writing it
+        // so they don't hide each other.
+        let mut blended = vec![];
+        for (model, model_matrix) in scene.get_models() {
+            let model_transform: [[f32; 4]; 4] = model_matrix.into();
+            let normal_matrix: [[f32; 3]; 3] = transform::normal_matrix(&model_matrix).into();
             for mesh_object in model.get_mesh_objects() {
-                let uniforms = &uniform! {
-                    model: model.get_transform(),
-                    lightColor: [1f32, 0.9f32, 0.66f32],
-                    ambientIntensity: 0.1f32,
-                    lightPosition: *light_position,
-                    view_proj: view_proj,
-                    tex: mesh_object.get_diffuse_texture(),
-                    shadowMap: shadow_map,
-                    light_space_matrix: light_matrix,
-                    texelSize: texel_size,
-                    frustumSize: frustum_size,
-                    distribution: self.shadow_render_system.get_poisson_disk_texture(),
-                    ambientColor: *mesh_object.get_ambient_color(),
-                    diffuseColor: *mesh_object.get_diffuse_color(),
-                    specularColor: *mesh_object.get_specular_color()
-                };
+                let world_sphere = mesh_object.get_bounding_sphere().transformed(&model_matrix);
+                if !frustum.intersects_sphere(&world_sphere) {
+                    continue;
+                }
 
-                target
-                    .draw(
-                        mesh_object.get_vertices(),
-                        &indices,
-                        self.model_render_system.get_shader_program(),
-                        uniforms,
-                        &self.scene_draw_params,
-                    )
-                    .unwrap();
+                if mesh_object.get_material().is_blended() {
+                    let depth = camera
+                        .get_forward()
+                        .dot(world_sphere.center - camera.get_view_position());
+                    blended.push((depth, mesh_object, model_transform, normal_matrix));
+                } else {
+                    draw(mesh_object, model_transform, normal_matrix, draw_params);
+                }
             }
         }
+
+        let blended_draw_params = glium::DrawParameters {
+            depth: glium::Depth {
+                write: false,
+                ..draw_params.depth
+            },
+            blend: glium::Blend::alpha_blending(),
+            ..draw_params.clone()
+        };
+        blended.sort_by(|(a, ..), (b, ..)| b.total_cmp(a));
+        for (_, mesh_object, model_transform, normal_matrix) in blended {
+            draw(
+                mesh_object,
+                model_transform,
+                normal_matrix,
+                &blended_draw_params,
+            );
+        }
+    }
+}
+
+/// Width of the view the shadow filter scales its light size by. In
+/// perspective this is the width the filter was tuned against: the near plane
+/// of the original fixed 45 degree camera, with the half angle passed to tan
+/// in radians. It deliberately ignores the field of view, so zooming doesn't
+/// change the penumbrae. An orthographic view uses its actual width.
+fn shadow_frustum_size(projection: &Projection) -> f32 {
+    let aspect = *projection.get_aspect_ratio();
+    match projection.get_mode() {
+        ProjectionMode::Perspective => 2.0 * 0.1 * (45.0f32 * 0.5).tan() * aspect,
+        ProjectionMode::Orthographic => projection.get_orthographic_height() * aspect,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Deg;
+
+    #[test]
+    fn shadow_frustum_size_follows_the_orthographic_view_only() {
+        let mut projection = Projection::new(800, 400, Deg(45.0), 0.1, 100.0);
+        let perspective = shadow_frustum_size(&projection);
+        projection.set_fovy(Deg(20.0));
+        assert_eq!(shadow_frustum_size(&projection), perspective);
+
+        projection.set_mode(ProjectionMode::Orthographic);
+        projection.set_orthographic_height(6.0);
+        assert_eq!(shadow_frustum_size(&projection), 12.0);
     }
 }
@@ -0,0 +1,170 @@
+use cgmath::{Matrix4, SquareMatrix};
+
+use crate::model::Model;
+use crate::transform::Transform;
+
+/// Index of a node in its `Scene`.
+pub type NodeId = usize;
+
+pub struct Node {
+    name: String,
+    children: Vec<NodeId>,
+    transform: Transform,
+    world_matrix: Matrix4<f32>,
+    model: Option<Model>,
+}
+
+impl Node {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_children(&self) -> &[NodeId] {
+        &self.children
+    }
+
+    /// Placement relative to the parent node, or to the world for roots.
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// The node's world matrix as of the last `Scene::update_world_matrices`.
+    pub fn get_world_matrix(&self) -> &Matrix4<f32> {
+        &self.world_matrix
+    }
+
+    pub fn get_model(&self) -> Option<&Model> {
+        self.model.as_ref()
+    }
+
+    pub fn get_model_mut(&mut self) -> Option<&mut Model> {
+        self.model.as_mut()
+    }
+}
+
+/// A hierarchy of nodes, each with a local transform and optionally a model.
+/// Nodes are never removed, so a `NodeId` stays valid for the scene's life.
+#[derive(Default)]
+pub struct Scene {
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an empty node under `parent`, or as a root when `None`.
+    pub fn add_node(&mut self, name: &str, parent: Option<NodeId>, transform: Transform) -> NodeId {
+        let id = self.nodes.len();
+        match parent {
+            Some(parent) => self.nodes[parent].children.push(id),
+            None => self.roots.push(id),
+        }
+        self.nodes.push(Node {
+            name: name.to_owned(),
+            children: vec![],
+            transform,
+            world_matrix: Matrix4::identity(),
+            model: None,
+        });
+        id
+    }
+
+    pub fn set_model(&mut self, id: NodeId, model: Model) {
+        self.nodes[id].model = Some(model);
+    }
+
+    pub fn get_node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn get_node_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id]
+    }
+
+    /// Nodes without a parent, in the order they were added.
+    pub fn get_roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Composes every node's local transform with its ancestors'. Call once
+    /// per frame after transforms change and before rendering.
+    pub fn update_world_matrices(&mut self) {
+        let mut stack: Vec<(NodeId, Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, Matrix4::identity()))
+            .collect();
+        while let Some((id, parent_matrix)) = stack.pop() {
+            let node = &mut self.nodes[id];
+            node.world_matrix = parent_matrix * node.transform.get_matrix();
+            let world_matrix = node.world_matrix;
+            stack.extend(node.children.iter().map(|&child| (child, world_matrix)));
+        }
+    }
+
+    /// Every node holding a model, with the model.
+    pub fn get_model_nodes(&self) -> impl Iterator<Item = (NodeId, &Model)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(id, node)| node.model.as_ref().map(|model| (id, model)))
+    }
+
+    /// Every model in the scene with the matrix taking its vertices to world
+    /// space: the node's world matrix followed by the model's own transform.
+    pub fn get_models(&self) -> impl Iterator<Item = (&Model, Matrix4<f32>)> {
+        self.nodes.iter().filter_map(|node| {
+            node.model.as_ref().map(|model| {
+                (
+                    model,
+                    node.world_matrix * model.get_transform().get_matrix(),
+                )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Point3, Transform as _, Vector3};
+
+    fn translation(x: f32, y: f32, z: f32) -> Transform {
+        Transform {
+            translation: Vector3::new(x, y, z),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn children_follow_their_parents() {
+        let mut scene = Scene::new();
+        let root = scene.add_node("root", None, translation(1.0, 0.0, 0.0));
+        let child = scene.add_node("child", Some(root), Transform::from_scale(2.0));
+        let grandchild = scene.add_node("grandchild", Some(child), translation(0.0, 1.0, 0.0));
+        scene.update_world_matrices();
+
+        let origin = |scene: &Scene, id| {
+            scene
+                .get_node(id)
+                .get_world_matrix()
+                .transform_point(Point3::new(0.0, 0.0, 0.0))
+        };
+        assert_eq!(origin(&scene, grandchild), Point3::new(1.0, 2.0, 0.0));
+        assert_eq!(scene.get_roots(), &[root]);
+        assert_eq!(scene.get_node(root).get_children(), &[child]);
+
+        scene
+            .get_node_mut(root)
+            .set_transform(translation(0.0, 0.0, 5.0));
+        scene.update_world_matrices();
+        assert_eq!(origin(&scene, grandchild), Point3::new(0.0, 2.0, 5.0));
+    }
+}
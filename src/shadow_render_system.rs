@@ -6,7 +6,7 @@ pub struct ShadowRenderSystem {
     program: glium::program::Program,
     projection_matrix: cgmath::Matrix4<f32>,
     drawable_shadow_texture: std::rc::Rc<SrgbTexture2d>,
-    poisson_disk: glium::texture::SrgbTexture1d
+    poisson_disk: glium::texture::SrgbTexture1d,
 }
 
 pub const SHADOW_SIZE: u32 = 1024 * 2;
@@ -48,8 +48,7 @@ impl ShadowRenderSystem {
 
         let points = points
             .iter()
-            .map(|point| vec![point[0] as f32, point[1] as f32, 0.0])
-            .flatten()
+            .flat_map(|point| vec![point[0] as f32, point[1] as f32, 0.0])
             .collect();
 
         let distribution = glium::texture::RawImage1d::from_raw_rgb(points);
@@ -65,7 +64,7 @@ impl ShadowRenderSystem {
             program,
             projection_matrix,
             drawable_shadow_texture: shadow_texture_to_render,
-            poisson_disk
+            poisson_disk,
         }
     }
 
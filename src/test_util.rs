@@ -0,0 +1,26 @@
+use std::path::PathBuf;
+
+/// Writes `files` into a fresh scratch directory and returns it. Each test
+/// needs its own `test_name`, since tests run in parallel.
+pub fn scratch_dir_with(test_name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("diffuse_gi_{}_{}", test_name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    for (name, contents) in files {
+        let file = dir.join(name);
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, contents).unwrap();
+    }
+    dir
+}
+
+/// Copies the crate model into a fresh scratch directory and returns the
+/// OBJ's path there, so loading it leaves no mesh cache in the repository.
+pub fn scratch_crate(test_name: &str) -> PathBuf {
+    let files = ["crate.obj", "crate.mtl"].map(|name| (name, std::fs::read(name).unwrap()));
+    let files: Vec<(&str, &[u8])> = files
+        .iter()
+        .map(|(name, contents)| (*name, contents.as_slice()))
+        .collect();
+    scratch_dir_with(test_name, &files).join("crate.obj")
+}
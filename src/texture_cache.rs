@@ -0,0 +1,304 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use glium::texture::{MipmapsOption, RawImage2d, SrgbTexture2d, Texture2d};
+use glium::Display;
+use image::GenericImageView;
+
+/// Every color and data map is stored as 8-bit RGBA.
+const BYTES_PER_TEXEL: usize = 4;
+
+/// Bound in place of a color map that couldn't be found or loaded, so the
+/// failure is obvious on screen. Data maps get no such marker, since the
+/// shader would read it as real normals or roughness.
+const MISSING_TEXTURE_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+#[derive(Debug)]
+pub enum TextureLoadError {
+    Decode(image::ImageError),
+    /// An image referenced by a glTF couldn't be read or decoded.
+    Embedded(gltf::Error),
+    Upload(glium::texture::TextureCreationError),
+}
+
+impl fmt::Display for TextureLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Decode(source) => write!(f, "{}", source),
+            Self::Embedded(source) => write!(f, "{}", source),
+            Self::Upload(source) => write!(f, "upload failed: {}", source),
+        }
+    }
+}
+
+/// An 8-bit RGBA image decoded on the CPU and waiting for upload.
+pub struct ImageData {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ImageData {
+    /// Reads and decodes the image file at `path`. Safe to call from worker
+    /// threads.
+    pub fn decode(path: &Path) -> Result<Self, TextureLoadError> {
+        let image = image::io::Reader::open(path)
+            .map_err(|error| TextureLoadError::Decode(error.into()))?
+            .decode()
+            .map_err(TextureLoadError::Decode)?;
+        let (width, height) = image.dimensions();
+        Ok(Self {
+            pixels: image.to_rgba8().into_raw(),
+            width,
+            height,
+        })
+    }
+
+    pub fn into_raw_image(self) -> RawImage2d<'static, u8> {
+        RawImage2d::from_raw_rgba(self.pixels, (self.width, self.height))
+    }
+
+    pub fn to_raw_image(&self) -> RawImage2d<'static, u8> {
+        RawImage2d::from_raw_rgba(self.pixels.clone(), (self.width, self.height))
+    }
+}
+
+/// Decoded GPU textures keyed by canonical file path, so a map referenced by
+/// many materials, or by several models, is decoded and uploaded once.
+#[derive(Default)]
+pub struct TextureCache {
+    srgb_textures: HashMap<PathBuf, Rc<SrgbTexture2d>>,
+    linear_textures: HashMap<PathBuf, Rc<Texture2d>>,
+    /// Keys of maps that failed to load, so each failure is reported once.
+    failed: HashSet<PathBuf>,
+    srgb_placeholder: Option<Rc<SrgbTexture2d>>,
+    linear_placeholder: Option<Rc<Texture2d>>,
+    srgb_missing: Option<Rc<SrgbTexture2d>>,
+}
+
+impl TextureCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the color map at `path`, loading it on first use. A map that
+    /// fails to load is replaced by the missing-texture placeholder.
+    pub fn get_srgb(&mut self, display: &Display, path: &Path) -> Rc<SrgbTexture2d> {
+        self.get_srgb_with(display, canonical_path(path), || load_raw_image(path))
+    }
+
+    /// Returns the data map at `path` without sRGB decoding, loading it on
+    /// first use, or `None` if it fails to load.
+    pub fn get_linear(&mut self, display: &Display, path: &Path) -> Option<Rc<Texture2d>> {
+        self.get_linear_with(display, canonical_path(path), || load_raw_image(path))
+    }
+
+    /// Returns the color map cached under `key`, calling `load` for its
+    /// pixels on first use. This covers images that don't live in their own
+    /// file, such as those embedded in a glTF.
+    pub fn get_srgb_with<F>(
+        &mut self,
+        display: &Display,
+        key: PathBuf,
+        load: F,
+    ) -> Rc<SrgbTexture2d>
+    where
+        F: FnOnce() -> Result<RawImage2d<'static, u8>, TextureLoadError>,
+    {
+        if let Some(texture) = self.srgb_textures.get(&key) {
+            return texture.clone();
+        }
+        if self.failed.contains(&key) {
+            return self.get_srgb_missing(display);
+        }
+
+        let texture = load().and_then(|image| {
+            SrgbTexture2d::with_mipmaps(display, image, MipmapsOption::AutoGeneratedMipmaps)
+                .map_err(TextureLoadError::Upload)
+        });
+        match texture {
+            Ok(texture) => {
+                let texture = Rc::new(texture);
+                self.srgb_textures.insert(key, texture.clone());
+                texture
+            }
+            Err(error) => {
+                report_texture_failure(&key, &error);
+                self.failed.insert(key);
+                self.get_srgb_missing(display)
+            }
+        }
+    }
+
+    /// Returns the data map cached under `key`, calling `load` for its pixels
+    /// on first use, or `None` if it fails to load.
+    pub fn get_linear_with<F>(
+        &mut self,
+        display: &Display,
+        key: PathBuf,
+        load: F,
+    ) -> Option<Rc<Texture2d>>
+    where
+        F: FnOnce() -> Result<RawImage2d<'static, u8>, TextureLoadError>,
+    {
+        if let Some(texture) = self.linear_textures.get(&key) {
+            return Some(texture.clone());
+        }
+        if self.failed.contains(&key) {
+            return None;
+        }
+
+        let texture = load().and_then(|image| {
+            Texture2d::with_mipmaps(display, image, MipmapsOption::AutoGeneratedMipmaps)
+                .map_err(TextureLoadError::Upload)
+        });
+        match texture {
+            Ok(texture) => {
+                let texture = Rc::new(texture);
+                self.linear_textures.insert(key, texture.clone());
+                Some(texture)
+            }
+            Err(error) => {
+                report_texture_failure(&key, &error);
+                self.failed.insert(key);
+                None
+            }
+        }
+    }
+
+    /// The shared 1x1 texture bound in place of a color map a material lacks.
+    pub fn get_srgb_placeholder(&mut self, display: &Display) -> Rc<SrgbTexture2d> {
+        self.srgb_placeholder
+            .get_or_insert_with(|| Rc::new(SrgbTexture2d::empty(display, 1, 1).unwrap()))
+            .clone()
+    }
+
+    /// The shared 1x1 texture bound in place of a data map a material lacks.
+    pub fn get_linear_placeholder(&mut self, display: &Display) -> Rc<Texture2d> {
+        self.linear_placeholder
+            .get_or_insert_with(|| Rc::new(Texture2d::empty(display, 1, 1).unwrap()))
+            .clone()
+    }
+
+    /// The shared magenta texture bound in place of a color map that couldn't
+    /// be found or loaded.
+    pub fn get_srgb_missing(&mut self, display: &Display) -> Rc<SrgbTexture2d> {
+        self.srgb_missing
+            .get_or_insert_with(|| {
+                Rc::new(
+                    SrgbTexture2d::with_mipmaps(
+                        display,
+                        missing_texture_image(),
+                        MipmapsOption::AutoGeneratedMipmaps,
+                    )
+                    .unwrap(),
+                )
+            })
+            .clone()
+    }
+
+    /// Records that the map cached under `key` failed to decode elsewhere, on
+    /// a loader thread, so it is watched and skipped like any other failure.
+    pub fn record_failure(&mut self, key: PathBuf) {
+        self.failed.insert(key);
+    }
+
+    /// Drops the textures cached under `key`, or its recorded failure, so the
+    /// next request loads the file again. Returns whether there were any.
+    pub fn evict(&mut self, key: &Path) -> bool {
+        let srgb = self.srgb_textures.remove(key).is_some();
+        let linear = self.linear_textures.remove(key).is_some();
+        let failed = self.failed.remove(key);
+        srgb || linear || failed
+    }
+
+    /// Keys of every cached map, failed ones included, so background loaders
+    /// can skip decoding images that are already on the GPU or known to be
+    /// broken.
+    pub fn get_cached_paths(&self) -> HashSet<PathBuf> {
+        self.srgb_textures
+            .keys()
+            .chain(self.linear_textures.keys())
+            .chain(self.failed.iter())
+            .cloned()
+            .collect()
+    }
+
+    /// Number of textures uploaded from maps; placeholders aren't counted.
+    pub fn get_texture_count(&self) -> usize {
+        self.srgb_textures.len() + self.linear_textures.len()
+    }
+
+    /// Approximate GPU memory held by the cached textures and their mipmap
+    /// chains, in bytes.
+    pub fn get_memory_usage(&self) -> usize {
+        let srgb = self
+            .srgb_textures
+            .values()
+            .map(|texture| texture_bytes(texture.width(), texture.height()));
+        let linear = self
+            .linear_textures
+            .values()
+            .map(|texture| texture_bytes(texture.width(), texture.height()));
+        srgb.chain(linear).sum()
+    }
+}
+
+fn texture_bytes(width: u32, height: u32) -> usize {
+    let (mut width, mut height) = (width as usize, height as usize);
+    let mut bytes = width * height * BYTES_PER_TEXEL;
+    while width > 1 || height > 1 {
+        width = (width / 2).max(1);
+        height = (height / 2).max(1);
+        bytes += width * height * BYTES_PER_TEXEL;
+    }
+    bytes
+}
+
+/// Different spellings of one file (`./a/../b.png`, symlinks) share a key.
+pub fn canonical_path(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Warns that the map cached under `key` is being replaced by a
+/// placeholder.
+pub fn report_texture_failure(key: &Path, error: &TextureLoadError) {
+    eprintln!(
+        "warning: texture {} failed to load ({}), using placeholder",
+        key.display(),
+        error
+    );
+}
+
+fn missing_texture_image() -> RawImage2d<'static, u8> {
+    RawImage2d::from_raw_rgba(MISSING_TEXTURE_COLOR.to_vec(), (1, 1))
+}
+
+fn load_raw_image(path: &Path) -> Result<RawImage2d<'static, u8>, TextureLoadError> {
+    ImageData::decode(path).map(ImageData::into_raw_image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::scratch_dir_with;
+
+    #[test]
+    fn unreadable_images_are_errors() {
+        let dir = scratch_dir_with("texture_cache_unreadable", &[("garbage.png", b"not a png")]);
+        let garbage = dir.join("garbage.png");
+
+        assert!(matches!(
+            ImageData::decode(&dir.join("missing.png")),
+            Err(TextureLoadError::Decode(_))
+        ));
+        assert!(matches!(
+            ImageData::decode(&garbage),
+            Err(TextureLoadError::Decode(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+use cgmath::{Matrix, Matrix3, Matrix4, One, Quaternion, SquareMatrix, Vector3};
+
+/// Placement of an object: scaled per axis, then rotated, then translated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vector3<f32>,
+    pub rotation: Quaternion<f32>,
+    pub scale: Vector3<f32>,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self {
+            translation: Vector3::new(0.0, 0.0, 0.0),
+            rotation: Quaternion::one(),
+            scale: Vector3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    pub fn from_scale(scale: f32) -> Self {
+        Self {
+            scale: Vector3::new(scale, scale, scale),
+            ..Default::default()
+        }
+    }
+
+    pub fn get_matrix(&self) -> Matrix4<f32> {
+        Matrix4::from_translation(self.translation)
+            * Matrix4::from(self.rotation)
+            * Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// The inverse transpose of `matrix`'s upper 3x3, which keeps normals
+/// perpendicular to their surface under non-uniform scale. Normals still need
+/// renormalizing afterwards. A matrix that flattens space has no inverse, so
+/// its upper 3x3 is used as is.
+pub fn normal_matrix(matrix: &Matrix4<f32>) -> Matrix3<f32> {
+    let upper = Matrix3::from_cols(
+        matrix.x.truncate(),
+        matrix.y.truncate(),
+        matrix.z.truncate(),
+    );
+    upper.invert().map_or(upper, |inverse| inverse.transpose())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, InnerSpace, Point3, Rotation3, Transform as _};
+
+    #[test]
+    fn matrix_scales_then_rotates_then_translates() {
+        let transform = Transform {
+            translation: Vector3::new(0.0, 0.0, 5.0),
+            rotation: Quaternion::from_angle_z(Deg(90.0)),
+            scale: Vector3::new(2.0, 1.0, 1.0),
+        };
+
+        let point = transform
+            .get_matrix()
+            .transform_point(Point3::new(1.0, 0.0, 0.0));
+        assert!(
+            (point - Point3::new(0.0, 2.0, 5.0)).magnitude() < 1e-5,
+            "{:?}",
+            point
+        );
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular() {
+        let transform = Transform {
+            translation: Vector3::new(1.0, 2.0, 3.0),
+            rotation: Quaternion::from_angle_y(Deg(30.0)),
+            scale: Vector3::new(1.0, 4.0, 0.5),
+        };
+        let matrix = transform.get_matrix();
+        let normals = normal_matrix(&matrix);
+
+        // A slanted surface stays perpendicular to its normal once stretched.
+        let tangent = Vector3::new(1.0, -1.0, 0.0);
+        let normal = Vector3::new(1.0, 1.0, 0.0);
+        let tangent = (matrix * tangent.extend(0.0)).truncate();
+        assert!(tangent.dot(normals * normal).abs() < 1e-5);
+
+        // For a single transform it is the rotation over the scale.
+        let expected = Matrix3::from(transform.rotation)
+            * Matrix3::from_diagonal(Vector3::new(1.0, 0.25, 2.0));
+        for (actual, expected) in [normals.x, normals.y, normals.z]
+            .iter()
+            .zip([expected.x, expected.y, expected.z])
+        {
+            assert!((actual - expected).magnitude() < 1e-5);
+        }
+    }
+}